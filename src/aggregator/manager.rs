@@ -26,6 +26,8 @@ use crate::notifier::generic::Notification;
 use crate::notifier::Error;
 use crate::prober::manager::STORE as PROBER_STORE;
 use crate::prober::mode::Mode;
+use crate::prober::persist::save as save_store_prober;
+use crate::prober::states::ServiceStatesNotifierAcknowledgement;
 use crate::prober::status::Status;
 use crate::APP_CONF;
 
@@ -71,9 +73,16 @@ static TIME_NOW_FORMATTER: Lazy<Vec<FormatItem<'static>>> = Lazy::new(|| {
 
 const AGGREGATE_INTERVAL_SECONDS: u64 = 10;
 
+#[derive(Debug)]
+pub enum AcknowledgeError {
+    NotDead,
+    InvalidDuration,
+}
+
 struct BumpedStates {
     status: Status,
     replicas: Vec<String>,
+    messages: Vec<(String, String)>,
     changed: bool,
     startup: bool,
 }
@@ -89,8 +98,9 @@ fn check_child_status(parent_status: &Status, child_status: &Status) -> Option<S
 }
 
 fn scan_and_bump_states() -> Option<BumpedStates> {
-    let notify = &APP_CONF.notify;
+    let notify = &APP_CONF.load().notify;
     let mut bumped_replicas = Vec::new();
+    let mut bumped_messages = Vec::new();
 
     let mut store = PROBER_STORE.write();
 
@@ -119,7 +129,9 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
                             {
                                 if duration_since_report
                                     >= (replica_report.interval
-                                        + Duration::from_secs(APP_CONF.metrics.push_delay_dead))
+                                        + Duration::from_secs(
+                                            APP_CONF.load().metrics.push_delay_dead,
+                                        ))
                                 {
                                     tracing::debug!(
                                         "replica: {}:{}:{} is dead because it didnt report in a while",
@@ -134,9 +146,10 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
                         // Compare system load indices and compute a new status?
                         if replica_status == Status::Healthy {
                             if let Some(ref replica_load) = replica.load {
-                                if (replica_load.cpu > APP_CONF.metrics.push_system_cpu_sick_above)
+                                if (replica_load.cpu
+                                    > APP_CONF.load().metrics.push_system_cpu_sick_above)
                                     || (replica_load.ram
-                                        > APP_CONF.metrics.push_system_ram_sick_above)
+                                        > APP_CONF.load().metrics.push_system_ram_sick_above)
                                 {
                                     tracing::debug!(
                                         "replica: {}:{}:{} is sick because it is overloaded",
@@ -173,7 +186,9 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
                             {
                                 if duration_since_report
                                     >= (replica_report.interval
-                                        + Duration::from_secs(APP_CONF.metrics.local_delay_dead))
+                                        + Duration::from_secs(
+                                            APP_CONF.load().metrics.local_delay_dead,
+                                        ))
                                 {
                                     tracing::debug!(
                                         "replica: {}:{}:{} is dead because it didnt report in a while",
@@ -209,6 +224,16 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
                     bumped_replicas.push(format!("{}:{}:{}", probe_id, node_id, replica_id));
                 }
 
+                // Append failure message? (eg. captured script output)
+                if replica_status != Status::Healthy {
+                    if let Some(ref message) = replica.message {
+                        bumped_messages.push((
+                            format!("{}:{}:{}", probe_id, node_id, replica_id),
+                            message.clone(),
+                        ));
+                    }
+                }
+
                 replica.status = replica_status;
             }
 
@@ -256,6 +281,14 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
     // Reset the reminder states whenever we are not dead (yet, stored status changed)
     if has_changed && general_status != Status::Dead {
         store.states.notifier.reminder_backoff_counter = 1;
+    }
+
+    // Clear any acknowledgement whenever the status changed (it applies to a given outage only)
+    if has_changed {
+        if store.states.notifier.acknowledgement.take().is_some() {
+            tracing::info!("cleared outage acknowledgement, as status changed");
+        }
+
         store.states.notifier.reminder_ignore_until = None;
     }
 
@@ -272,6 +305,7 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
                 //   then the value is 1 at any time, thus not impacting the interval.
                 let reminder_backoff_counter = store.states.notifier.reminder_backoff_counter;
                 let reminder_ignore_until = store.states.notifier.reminder_ignore_until;
+                let reminder_acknowledged = store.states.notifier.acknowledgement.is_some();
                 let reminder_interval_backoff = Duration::from_secs(
                     reminder_interval
                         * u64::from(reminder_backoff_counter)
@@ -279,11 +313,13 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
                 );
 
                 // Check if reminders should be ignored for now?
+                // Notice: an outage acknowledged without a duration silences reminders until \
+                //   the status changes again.
                 let should_ignore_reminders =
                     if let Some(reminder_ignore_until) = reminder_ignore_until {
                         SystemTime::now() < reminder_ignore_until
                     } else {
-                        false
+                        reminder_acknowledged
                     };
 
                 tracing::debug!(
@@ -338,6 +374,7 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
         Some(BumpedStates {
             status: general_status,
             replicas: bumped_replicas,
+            messages: bumped_messages,
             changed: has_changed,
             startup: false,
         })
@@ -346,6 +383,36 @@ fn scan_and_bump_states() -> Option<BumpedStates> {
     }
 }
 
+pub fn acknowledge(by: &str, duration: Option<Duration>) -> Result<(), AcknowledgeError> {
+    let mut store = PROBER_STORE.write();
+
+    // Only an ongoing outage can be acknowledged
+    if store.states.status != Status::Dead {
+        return Err(AcknowledgeError::NotDead);
+    }
+
+    let now = SystemTime::now();
+
+    // Notice: the duration comes from the caller, and might not fit in the system time
+    let reminder_ignore_until = match duration {
+        Some(duration) => Some(
+            now.checked_add(duration)
+                .ok_or(AcknowledgeError::InvalidDuration)?,
+        ),
+        None => None,
+    };
+
+    tracing::info!("acknowledged outage by: {} (duration: {:?})", by, duration);
+
+    store.states.notifier.reminder_ignore_until = reminder_ignore_until;
+    store.states.notifier.acknowledgement = Some(ServiceStatesNotifierAcknowledgement {
+        by: by.to_string(),
+        time: now,
+    });
+
+    Ok(())
+}
+
 fn time_now_as_string() -> String {
     time::OffsetDateTime::now_utc()
         .format(&TIME_NOW_FORMATTER)
@@ -353,12 +420,26 @@ fn time_now_as_string() -> String {
 }
 
 fn dispatch_startup_notification() -> Result<(), Vec<Error>> {
-    if APP_CONF.notify.startup_notification {
+    // Notice: do not notify that systems are healthy if the restored status says otherwise (ie. \
+    //   if we restarted during an outage)
+    let restored_status = PROBER_STORE.read().states.status.clone();
+
+    if restored_status != Status::Healthy {
+        tracing::info!(
+            "skipping aggregate startup notification, as restored status is: {:?}",
+            restored_status
+        );
+
+        return Ok(());
+    }
+
+    if APP_CONF.load().notify.startup_notification {
         tracing::debug!("sending aggregate startup notification...");
 
         notify(&BumpedStates {
             status: Status::Healthy,
             replicas: Vec::new(),
+            messages: Vec::new(),
             changed: true,
             startup: true,
         })?;
@@ -382,11 +463,16 @@ macro_rules! notifier {
 }
 
 fn notify(bumped_states: &BumpedStates) -> Result<(), Vec<Error>> {
-    let notify = &APP_CONF.notify;
+    let notify = &APP_CONF.load().notify;
     let notification = Notification {
         status: &bumped_states.status,
         time: time_now_as_string(),
         replicas: bumped_states.replicas.iter().map(String::as_str).collect(),
+        messages: bumped_states
+            .messages
+            .iter()
+            .map(|(replica, message)| (replica.as_str(), message.as_str()))
+            .collect(),
         changed: bumped_states.changed,
         startup: bumped_states.startup,
     };
@@ -500,6 +586,9 @@ pub fn run() -> Result<(), Vec<Error>> {
         // Should notify after bump?
         let bumped_states = scan_and_bump_states();
 
+        // Persist states before notifying (as a notification may fail)
+        save_store_prober();
+
         if let Some(ref bumped_states_inner) = bumped_states {
             notify(bumped_states_inner)?;
         }
@@ -46,6 +46,10 @@ pub fn metrics_poll_retry() -> u64 {
     2
 }
 
+pub fn metrics_poll_retry_delay() -> u64 {
+    500
+}
+
 pub fn metrics_poll_http_status_healthy_above() -> u16 {
     200
 }
@@ -62,6 +66,26 @@ pub fn metrics_poll_delay_sick() -> u64 {
     5
 }
 
+pub fn metrics_poll_tls_expiry_sick_below() -> u32 {
+    14
+}
+
+pub fn metrics_poll_icmp_count() -> u16 {
+    1
+}
+
+pub fn metrics_poll_icmp_interval() -> u64 {
+    200
+}
+
+pub fn metrics_poll_icmp_loss_sick_above() -> u8 {
+    0
+}
+
+pub fn metrics_poll_icmp_loss_dead_above() -> u8 {
+    50
+}
+
 pub fn poll_parallelism() -> u16 {
     4
 }
@@ -86,6 +110,10 @@ pub fn script_parallelism() -> u16 {
     2
 }
 
+pub fn metrics_script_timeout() -> u64 {
+    60
+}
+
 pub fn metrics_local_delay_dead() -> u64 {
     40
 }
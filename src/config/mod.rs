@@ -17,6 +17,7 @@
 
 mod defaults;
 
+pub mod check;
 pub mod notify;
 pub mod plugins;
 pub mod probe;
@@ -41,6 +42,7 @@ pub struct Config {
     pub plugins: Plugins,
     pub notify: Notify,
     pub probe: Probe,
+    pub state: Option<State>,
 }
 
 impl Config {
@@ -52,6 +54,15 @@ impl Config {
 
         s.try_deserialize()
     }
+
+    #[cfg(test)]
+    pub fn parse(toml: &str) -> Result<Self, ConfigError> {
+        let s = config::Config::builder()
+            .add_source(File::from_str(toml, config::FileFormat::Toml))
+            .build()?;
+
+        s.try_deserialize()
+    }
 }
 
 #[derive(Deserialize)]
@@ -75,6 +86,11 @@ pub struct Assets {
     pub path: PathBuf,
 }
 
+#[derive(Deserialize)]
+pub struct State {
+    pub path: PathBuf,
+}
+
 #[derive(Deserialize)]
 pub struct Branding {
     #[serde(default = "defaults::branding_page_title")]
@@ -99,6 +115,9 @@ pub struct Metrics {
     #[serde(default = "defaults::metrics_poll_retry")]
     pub poll_retry: u64,
 
+    #[serde(default = "defaults::metrics_poll_retry_delay")]
+    pub poll_retry_delay: u64,
+
     #[serde(default = "defaults::metrics_poll_http_status_healthy_above")]
     pub poll_http_status_healthy_above: u16,
 
@@ -111,6 +130,21 @@ pub struct Metrics {
     #[serde(default = "defaults::metrics_poll_delay_sick")]
     pub poll_delay_sick: u64,
 
+    #[serde(default = "defaults::metrics_poll_tls_expiry_sick_below")]
+    pub poll_tls_expiry_sick_below: u32,
+
+    #[serde(default = "defaults::metrics_poll_icmp_count")]
+    pub poll_icmp_count: u16,
+
+    #[serde(default = "defaults::metrics_poll_icmp_interval")]
+    pub poll_icmp_interval: u64,
+
+    #[serde(default = "defaults::metrics_poll_icmp_loss_sick_above")]
+    pub poll_icmp_loss_sick_above: u8,
+
+    #[serde(default = "defaults::metrics_poll_icmp_loss_dead_above")]
+    pub poll_icmp_loss_dead_above: u8,
+
     #[serde(default = "defaults::poll_parallelism")]
     pub poll_parallelism: u16,
 
@@ -129,6 +163,9 @@ pub struct Metrics {
     #[serde(default = "defaults::script_parallelism")]
     pub script_parallelism: u16,
 
+    #[serde(default = "defaults::metrics_script_timeout")]
+    pub script_timeout: u64,
+
     #[serde(default = "defaults::metrics_local_delay_dead")]
     pub local_delay_dead: u64,
 }
@@ -138,16 +175,23 @@ impl Default for Metrics {
         Self {
             poll_interval: defaults::metrics_poll_interval(),
             poll_retry: defaults::metrics_poll_retry(),
+            poll_retry_delay: defaults::metrics_poll_retry_delay(),
             poll_http_status_healthy_above: defaults::metrics_poll_http_status_healthy_above(),
             poll_http_status_healthy_below: defaults::metrics_poll_http_status_healthy_below(),
             poll_delay_dead: defaults::metrics_poll_delay_dead(),
             poll_delay_sick: defaults::metrics_poll_delay_sick(),
+            poll_tls_expiry_sick_below: defaults::metrics_poll_tls_expiry_sick_below(),
+            poll_icmp_count: defaults::metrics_poll_icmp_count(),
+            poll_icmp_interval: defaults::metrics_poll_icmp_interval(),
+            poll_icmp_loss_sick_above: defaults::metrics_poll_icmp_loss_sick_above(),
+            poll_icmp_loss_dead_above: defaults::metrics_poll_icmp_loss_dead_above(),
             poll_parallelism: defaults::poll_parallelism(),
             push_delay_dead: defaults::metrics_push_delay_dead(),
             push_system_cpu_sick_above: defaults::metrics_push_system_cpu_sick_above(),
             push_system_ram_sick_above: defaults::metrics_push_system_ram_sick_above(),
             script_interval: defaults::metrics_script_interval(),
             script_parallelism: defaults::script_parallelism(),
+            script_timeout: defaults::metrics_script_timeout(),
             local_delay_dead: defaults::metrics_local_delay_dead(),
         }
     }
@@ -23,15 +23,17 @@ pub struct Notify {
     pub telegram: Option<Telegram>,
     pub pushover: Option<Pushover>,
     pub gotify: Option<Gotify>,
+    #[cfg(feature = "notifier-xmpp")]
     pub xmpp: Option<Xmpp>,
     pub matrix: Option<Matrix>,
     pub webex: Option<WebEx>,
     pub webhook: Option<WebHook>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ReminderBackoffFunction {
     #[serde(rename = "none")]
+    #[default]
     None = 0,
 
     #[serde(rename = "linear")]
@@ -44,12 +46,6 @@ pub enum ReminderBackoffFunction {
     Cubic = 3,
 }
 
-impl Default for ReminderBackoffFunction {
-    fn default() -> Self {
-        Self::None
-    }
-}
-
 #[derive(Deserialize)]
 pub struct Email {
     pub to: String,
@@ -131,6 +127,7 @@ pub struct Gotify {
     pub reminders_only: bool,
 }
 
+#[cfg(feature = "notifier-xmpp")]
 #[derive(Deserialize)]
 pub struct Xmpp {
     pub to: String,
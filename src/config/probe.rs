@@ -1,6 +1,11 @@
+use std::path::PathBuf;
+
 use indexmap::IndexMap;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use url::Url;
 
 use crate::prober::mode::Mode;
 
@@ -13,6 +18,12 @@ pub struct Probe {
 #[derive(Deserialize)]
 pub struct Service {
     pub label: String,
+    pub poll_interval: Option<u64>,
+    pub poll_retry: Option<u64>,
+    pub poll_retry_delay: Option<u64>,
+    pub poll_delay_dead: Option<u64>,
+    pub poll_delay_sick: Option<u64>,
+    pub script_interval: Option<u64>,
     #[serde(default)]
     pub node: IndexMap<String, ServiceNode>,
 }
@@ -23,16 +34,72 @@ pub struct ServiceNode {
     pub mode: Mode,
     pub replicas: Option<Vec<String>>,
     pub scripts: Option<Vec<String>>,
+    pub poll_interval: Option<u64>,
+    pub poll_retry: Option<u64>,
+    pub poll_retry_delay: Option<u64>,
+    pub poll_delay_dead: Option<u64>,
+    pub poll_delay_sick: Option<u64>,
+    pub script_interval: Option<u64>,
     #[serde(default)]
     pub http_no_cache_buster: bool,
     #[serde(default)]
     #[serde(with = "http_serde::header_map")]
     pub http_headers: http::HeaderMap,
     pub http_method: Option<HttpMethod>,
+    pub http_timeout: Option<u64>,
+    #[serde(default)]
+    pub http_follow_redirects: bool,
+    pub http_max_redirects: Option<usize>,
+    pub http_proxy: Option<Url>,
     pub http_body: Option<String>,
+    pub http_status_healthy_above: Option<u16>,
+    pub http_status_healthy_below: Option<u16>,
+    #[serde(default)]
+    #[serde(with = "crate::util::regex_map")]
+    pub http_headers_healthy_match: IndexMap<String, Regex>,
     #[serde(default)]
     #[serde(with = "serde_regex")]
     pub http_body_healthy_match: Option<Regex>,
+    #[serde(default)]
+    #[serde(with = "serde_regex")]
+    pub http_body_unhealthy_match: Option<Regex>,
+    #[serde(default)]
+    pub http_body_json_assertions: Vec<HttpBodyJsonAssertion>,
+    #[serde(default)]
+    pub tls_insecure: bool,
+    pub tls_ca_path: Option<PathBuf>,
+    pub tls_client_certificate_path: Option<PathBuf>,
+    pub tls_client_key_path: Option<PathBuf>,
+    pub tcp_send: Option<String>,
+    #[serde(default)]
+    #[serde(with = "serde_regex")]
+    pub tcp_response_healthy_match: Option<Regex>,
+    pub udp_send: Option<String>,
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::util::hex::deserialize")]
+    pub udp_send_hex: Option<Vec<u8>>,
+    #[serde(default)]
+    #[serde(with = "serde_regex")]
+    pub udp_response_healthy_match: Option<Regex>,
+    pub ws_send: Option<String>,
+    #[serde(default)]
+    #[serde(with = "serde_regex")]
+    pub ws_response_healthy_match: Option<Regex>,
+    pub dns_expected_value: Option<String>,
+    pub icmp_count: Option<u16>,
+    pub icmp_interval: Option<u64>,
+    pub icmp_loss_sick_above: Option<u8>,
+    pub icmp_loss_dead_above: Option<u8>,
+    pub icmp_rtt_sick_above: Option<u64>,
+    pub icmp_rtt_dead_above: Option<u64>,
+    pub sql_query: Option<String>,
+    pub sql_expected_value: Option<String>,
+    pub sql_value_sick_above: Option<f64>,
+    pub sql_value_dead_above: Option<f64>,
+    pub script_timeout: Option<u64>,
+    #[serde(default)]
+    pub script_environment: IndexMap<String, String>,
+    pub script_working_directory: Option<PathBuf>,
     pub rabbitmq_queue: Option<String>,
     pub rabbitmq_queue_nack_healthy_below: Option<u32>,
     pub rabbitmq_queue_nack_dead_above: Option<u32>,
@@ -47,3 +114,210 @@ pub enum HttpMethod {
     Put,
     Patch,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpBodyJsonAssertion {
+    pub path: JsonPath,
+    pub operator: HttpBodyJsonOperator,
+    #[serde(default)]
+    pub value: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpBodyJsonOperator {
+    Exists,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+}
+
+impl HttpBodyJsonAssertion {
+    /// Checks whether the assertion holds against a JSON body.
+    ///
+    /// Notice: if the path selects multiple values, all of them must satisfy the assertion, while \
+    ///   a path that selects no value fails the assertion, whatever the operator.
+    pub fn is_match(&self, body: &Value) -> bool {
+        let selected = self.path.query(body).all();
+
+        if selected.is_empty() {
+            return false;
+        }
+
+        selected
+            .into_iter()
+            .all(|selected| self.is_match_value(selected))
+    }
+
+    fn is_match_value(&self, selected: &Value) -> bool {
+        match self.operator {
+            HttpBodyJsonOperator::Exists => true,
+            HttpBodyJsonOperator::Eq => Self::is_equal(selected, &self.value),
+            HttpBodyJsonOperator::Ne => !Self::is_equal(selected, &self.value),
+            HttpBodyJsonOperator::Gt => {
+                Self::compare(selected, &self.value).is_some_and(|ordering| ordering.is_gt())
+            }
+            HttpBodyJsonOperator::Ge => {
+                Self::compare(selected, &self.value).is_some_and(|ordering| ordering.is_ge())
+            }
+            HttpBodyJsonOperator::Lt => {
+                Self::compare(selected, &self.value).is_some_and(|ordering| ordering.is_lt())
+            }
+            HttpBodyJsonOperator::Le => {
+                Self::compare(selected, &self.value).is_some_and(|ordering| ordering.is_le())
+            }
+            HttpBodyJsonOperator::Contains => match (selected, &self.value) {
+                (Value::String(selected), Value::String(value)) => {
+                    selected.contains(value.as_str())
+                }
+                (Value::Array(selected), value) => {
+                    selected.iter().any(|item| Self::is_equal(item, value))
+                }
+                _ => false,
+            },
+        }
+    }
+
+    fn is_equal(left: &Value, right: &Value) -> bool {
+        // Notice: numbers are compared by value, as integers and floats are distinct in JSON \
+        //   values (eg. '1' and '1.0' are considered equal)
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => left.as_f64() == right.as_f64(),
+            _ => left == right,
+        }
+    }
+
+    fn compare(left: &Value, right: &Value) -> Option<std::cmp::Ordering> {
+        match (left, right) {
+            (Value::Number(left), Value::Number(right)) => {
+                left.as_f64()?.partial_cmp(&right.as_f64()?)
+            }
+            (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn assertion(
+        path: &str,
+        operator: HttpBodyJsonOperator,
+        value: Value,
+    ) -> HttpBodyJsonAssertion {
+        HttpBodyJsonAssertion {
+            path: JsonPath::parse(path).unwrap(),
+            operator,
+            value,
+        }
+    }
+
+    #[test]
+    fn multiple_matches_must_all_pass() {
+        let body =
+            json!({ "checks": [{ "status": "up" }, { "status": "up" }, { "status": "down" }] });
+
+        assert!(
+            !assertion("$.checks[*].status", HttpBodyJsonOperator::Eq, json!("up")).is_match(&body)
+        );
+        assert!(
+            assertion("$.checks[:2].status", HttpBodyJsonOperator::Eq, json!("up")).is_match(&body)
+        );
+        assert!(assertion(
+            "$.checks[*].status",
+            HttpBodyJsonOperator::Exists,
+            Value::Null
+        )
+        .is_match(&body));
+    }
+
+    #[test]
+    fn empty_selection_fails() {
+        let body = json!({ "checks": [] });
+
+        for operator in [
+            HttpBodyJsonOperator::Exists,
+            HttpBodyJsonOperator::Eq,
+            HttpBodyJsonOperator::Ne,
+            HttpBodyJsonOperator::Lt,
+        ] {
+            assert!(!assertion("$.checks[*].status", operator, json!("up")).is_match(&body));
+            assert!(!assertion("$.missing", operator, json!("up")).is_match(&body));
+        }
+    }
+
+    #[test]
+    fn equality() {
+        let body = json!({ "count": 1, "ratio": 0.5, "version": "1" });
+
+        // Numbers are compared by value, whether integers or floats
+        assert!(assertion("$.count", HttpBodyJsonOperator::Eq, json!(1.0)).is_match(&body));
+        assert!(assertion("$.ratio", HttpBodyJsonOperator::Eq, json!(0.5)).is_match(&body));
+
+        // Numbers never equal strings, even if they read the same
+        assert!(!assertion("$.count", HttpBodyJsonOperator::Eq, json!("1")).is_match(&body));
+        assert!(!assertion("$.version", HttpBodyJsonOperator::Eq, json!(1)).is_match(&body));
+        assert!(assertion("$.version", HttpBodyJsonOperator::Ne, json!(1)).is_match(&body));
+        assert!(assertion("$.version", HttpBodyJsonOperator::Eq, json!("1")).is_match(&body));
+    }
+
+    #[test]
+    fn contains() {
+        let body =
+            json!({ "message": "all systems operational", "regions": ["eu", "us"], "count": 10 });
+
+        assert!(assertion(
+            "$.message",
+            HttpBodyJsonOperator::Contains,
+            json!("operational")
+        )
+        .is_match(&body));
+        assert!(!assertion(
+            "$.message",
+            HttpBodyJsonOperator::Contains,
+            json!("degraded")
+        )
+        .is_match(&body));
+        assert!(
+            assertion("$.regions", HttpBodyJsonOperator::Contains, json!("eu")).is_match(&body)
+        );
+        assert!(
+            !assertion("$.regions", HttpBodyJsonOperator::Contains, json!("ap")).is_match(&body)
+        );
+
+        // Other values contain nothing
+        assert!(!assertion("$.count", HttpBodyJsonOperator::Contains, json!(1)).is_match(&body));
+    }
+
+    #[test]
+    fn ordering() {
+        let body = json!({ "latency": 120, "version": "1.10", "healthy": true, "tags": [] });
+
+        assert!(assertion("$.latency", HttpBodyJsonOperator::Lt, json!(200)).is_match(&body));
+        assert!(assertion("$.latency", HttpBodyJsonOperator::Ge, json!(120.0)).is_match(&body));
+        assert!(!assertion("$.latency", HttpBodyJsonOperator::Gt, json!(120)).is_match(&body));
+
+        // Strings are ordered lexicographically
+        assert!(assertion("$.version", HttpBodyJsonOperator::Lt, json!("1.9")).is_match(&body));
+
+        // Other values (or mixed types) are not ordered, thus fail any ordering operator
+        for operator in [
+            HttpBodyJsonOperator::Gt,
+            HttpBodyJsonOperator::Ge,
+            HttpBodyJsonOperator::Lt,
+            HttpBodyJsonOperator::Le,
+        ] {
+            assert!(!assertion("$.healthy", operator, json!(false)).is_match(&body));
+            assert!(!assertion("$.tags", operator, json!([])).is_match(&body));
+            assert!(!assertion("$.latency", operator, json!("120")).is_match(&body));
+        }
+    }
+}
@@ -24,29 +24,54 @@ mod prober;
 mod responder;
 mod util;
 
+use std::future::Future;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 use std::{process, thread};
 
+use arc_swap::ArcSwap;
 use clap::{value_parser, Arg, Command};
 use once_cell::sync::Lazy;
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
 use crate::aggregator::manager::run as run_aggregator;
+use crate::config::check::{check as check_config_problems, Problem};
 use crate::config::Config;
 use crate::prober::manager::{
-    initialize_store as initialize_store_prober, run_poll as run_poll_prober,
-    run_script as run_script_prober,
+    initialize_store as initialize_store_prober, make_probes as make_probes_prober,
+    reload_store as reload_store_prober, run_poll as run_poll_prober,
+    run_script as run_script_prober, InitializeError,
 };
+use crate::prober::persist::restore as restore_store_prober;
 
 struct AppArgs {
     config: PathBuf,
+    check_config: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReloadError {
+    #[error("could not load config: {0}")]
+    Config(#[from] ::config::ConfigError),
+
+    #[error("invalid config: {}", format_problems(.0))]
+    Check(Vec<Problem>),
+
+    #[error("invalid probe config: {0}")]
+    Probe(#[from] InitializeError),
+}
+
+fn format_problems(problems: &[Problem]) -> String {
+    problems
+        .iter()
+        .map(Problem::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
-pub static THREAD_NAME_PROBER_POLL: &str = "overvakt-prober-poll";
-pub static THREAD_NAME_PROBER_SCRIPT: &str = "overvakt-prober-script";
 pub static THREAD_NAME_AGGREGATOR: &str = "overvakt-aggregator";
 pub static THREAD_NAME_RESPONDER: &str = "overvakt-responder";
 
@@ -80,7 +105,9 @@ macro_rules! gen_spawn_managed {
 }
 
 static APP_ARGS: Lazy<AppArgs> = Lazy::new(make_app_args);
-static APP_CONF: Lazy<Config> = Lazy::new(|| {
+
+#[cfg(not(test))]
+static APP_CONF: Lazy<ArcSwap<Config>> = Lazy::new(|| {
     let c = match Config::new(&APP_ARGS.config) {
         Ok(v) => v,
         Err(e) => {
@@ -96,21 +123,18 @@ static APP_CONF: Lazy<Config> = Lazy::new(|| {
         process::exit(1);
     }
 
-    c
+    ArcSwap::from_pointee(c)
+});
+
+// Notice: tests do not get any argument, thus they run against a fixture configuration
+#[cfg(test)]
+static APP_CONF: Lazy<ArcSwap<Config>> = Lazy::new(|| {
+    ArcSwap::from_pointee(
+        Config::parse(include_str!("../tests/fixtures/overvakt.toml"))
+            .expect("invalid test config"),
+    )
 });
 
-gen_spawn_managed!(
-    "prober-poll",
-    spawn_poll_prober,
-    THREAD_NAME_PROBER_POLL,
-    run_poll_prober
-);
-gen_spawn_managed!(
-    "prober-script",
-    spawn_script_prober,
-    THREAD_NAME_PROBER_SCRIPT,
-    run_script_prober
-);
 gen_spawn_managed!(
     "aggregator",
     spawn_aggregator,
@@ -118,6 +142,26 @@ gen_spawn_managed!(
     run_aggregator
 );
 
+async fn spawn_managed_task<F, T>(name: &'static str, managed_fn: F)
+where
+    F: Fn() -> T,
+    T: Future<Output = ()> + Send + 'static,
+{
+    loop {
+        tracing::debug!("spawn managed task: {}", name);
+
+        // Block on worker task (await it)
+        if tokio::spawn(managed_fn()).await.is_ok() {
+            break;
+        }
+
+        tracing::error!("managed task crashed ({}), setting it up again", name);
+
+        // Prevents task start loop floods
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
 fn make_app_args() -> AppArgs {
     let matches = Command::new(clap::crate_name!())
         .version(clap::crate_version!())
@@ -131,6 +175,9 @@ fn make_app_args() -> AppArgs {
                 .default_value("./overvakt.toml")
                 .value_parser(value_parser!(PathBuf)),
         )
+        .subcommand(
+            Command::new("check-config").about("Check configuration file for problems, then exit"),
+        )
         .get_matches();
 
     // Generate owned app arguments
@@ -139,14 +186,101 @@ fn make_app_args() -> AppArgs {
             .get_one::<PathBuf>("config")
             .expect("invalid config value")
             .clone(),
+        check_config: matches.subcommand_matches("check-config").is_some(),
+    }
+}
+
+pub fn reload_config() -> Result<(), ReloadError> {
+    tracing::info!("reloading configuration from: {:?}", APP_ARGS.config);
+
+    // Notice: the running configuration is kept as-is if the new configuration is invalid. \
+    //   Server, assets and branding settings are bound upon startup, and require a restart.
+    let conf = Config::new(&APP_ARGS.config)?;
+
+    // Refuse any configuration that 'check-config' would reject (eg. a missing assets path)
+    let problems = check_config_problems(&conf);
+
+    if !problems.is_empty() {
+        return Err(ReloadError::Check(problems));
+    }
+
+    // Build the new probes first, so that the live store is left untouched if they are invalid
+    let probes = make_probes_prober(&conf)?;
+
+    // Notice: the configuration is swapped before the prober store gets reloaded, as reloading \
+    //   clears cached clients, which must only get rebuilt from the new configuration.
+    APP_CONF.store(Arc::new(conf));
+
+    reload_store_prober(probes);
+
+    tracing::info!("reloaded configuration");
+
+    Ok(())
+}
+
+// Notice: signals are only available on Unix, though the manager API reloads on all platforms
+#[cfg(unix)]
+async fn listen_reload_signal() {
+    let mut hangup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            tracing::error!("could not listen for reload signal (error: {})", err);
+
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        tracing::info!("received reload signal");
+
+        match tokio::task::spawn_blocking(reload_config).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => tracing::error!("could not reload configuration (error: {})", err),
+            Err(err) => tracing::error!("configuration reload crashed (error: {})", err),
+        }
+    }
+}
+
+fn check_config() -> i32 {
+    let conf = match Config::new(&APP_ARGS.config) {
+        Ok(conf) => conf,
+        Err(err) => {
+            eprintln!("{}: {}", APP_ARGS.config.display(), err);
+
+            return 1;
+        }
+    };
+
+    let problems = check_config_problems(&conf);
+
+    for problem in problems.iter() {
+        eprintln!("{}: {}", APP_ARGS.config.display(), problem);
+    }
+
+    if problems.is_empty() {
+        println!("{}: configuration is valid", APP_ARGS.config.display());
+
+        0
+    } else {
+        eprintln!(
+            "{}: found {} problem(s) in configuration",
+            APP_ARGS.config.display(),
+            problems.len()
+        );
+
+        1
     }
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
+    // Check configuration? (does not start the service)
+    if APP_ARGS.check_config {
+        process::exit(check_config());
+    }
+
     // Initialize shared logger
     let env_filter = EnvFilter::default().add_directive(
-        LevelFilter::from_str(&APP_CONF.server.log_level)
+        LevelFilter::from_str(&APP_CONF.load().server.log_level)
             .expect("invalid log level")
             .into(),
     );
@@ -158,18 +292,29 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("starting up");
 
-    // Initialize prober store
+    // Initialize prober store (and restore its persisted state, if any)
     initialize_store_prober();
-
-    // Spawn probes (background thread)
-    thread::spawn(spawn_poll_prober);
-    thread::spawn(spawn_script_prober);
+    restore_store_prober();
 
     // Spawn aggregator (background thread)
     thread::spawn(spawn_aggregator);
 
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(APP_CONF.load().server.workers)
+        .thread_name(THREAD_NAME_RESPONDER)
+        .enable_all()
+        .build()?;
+
+    // Spawn probes (background tasks)
+    runtime.spawn(spawn_managed_task("prober-poll", run_poll_prober));
+    runtime.spawn(spawn_managed_task("prober-script", run_script_prober));
+
+    // Listen for configuration reload signal (background task)
+    #[cfg(unix)]
+    runtime.spawn(listen_reload_signal());
+
     // Spawn Web responder (foreground thread)
-    responder::manager::run().await?;
+    runtime.block_on(responder::manager::run())?;
 
     tracing::info!("shutting down server");
     Ok(())
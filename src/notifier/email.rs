@@ -56,25 +56,33 @@ impl Notifier for EmailNotifier {
         if notification.startup {
             message.push_str(&format!(
                 "Status startup alert from: {}\n",
-                APP_CONF.branding.page_title
+                APP_CONF.load().branding.page_title
             ));
         } else if notification.changed {
             message.push_str(&format!(
                 "Status change report from: {}\n",
-                APP_CONF.branding.page_title
+                APP_CONF.load().branding.page_title
             ));
         } else {
             message.push_str(&format!(
                 "Status unchanged reminder from: {}\n",
-                APP_CONF.branding.page_title
+                APP_CONF.load().branding.page_title
             ));
         }
 
         message.push_str("\n--\n");
         message.push_str(&format!("Status: {:?}\n", notification.status));
         message.push_str(&format!("Nodes: {}\n", &nodes_label));
+
+        for message_line in notification.messages_lines() {
+            message.push_str(&format!("Message: {}\n", message_line));
+        }
+
         message.push_str(&format!("Time: {}\n", &notification.time));
-        message.push_str(&format!("URL: {}", APP_CONF.branding.page_url.as_str()));
+        message.push_str(&format!(
+            "URL: {}",
+            APP_CONF.load().branding.page_url.as_str()
+        ));
 
         message.push_str("\n--\n\n");
         message.push_str("To unsubscribe, please edit your status page configuration.");
@@ -85,7 +93,7 @@ impl Notifier for EmailNotifier {
         let email_message = MessageBuilder::new()
             .to(email_config.to.as_str().parse()?)
             .from(Mailbox::new(
-                Some(APP_CONF.branding.page_title.to_string()),
+                Some(APP_CONF.load().branding.page_title.to_string()),
                 email_config.from.as_str().parse()?,
             ))
             .subject(format!(
@@ -29,6 +29,7 @@ pub struct Notification<'a> {
     pub status: &'a Status,
     pub time: String,
     pub replicas: Vec<&'a str>,
+    pub messages: Vec<(&'a str, &'a str)>,
     pub changed: bool,
     pub startup: bool,
 }
@@ -93,6 +94,21 @@ impl<'a> Notification<'a> {
         Ok(())
     }
 
+    pub fn messages_lines(&self) -> Vec<String> {
+        // Notice: messages might span multiple lines (eg. script output), which get flattened \
+        //   so that each replica message fits on a single line.
+        self.messages
+            .iter()
+            .map(|(replica, message)| {
+                format!(
+                    "{}: {}",
+                    replica,
+                    message.lines().collect::<Vec<&str>>().join(" / ")
+                )
+            })
+            .collect()
+    }
+
     pub fn expected(&self, reminders_only: bool) -> bool {
         // Notification may not be expected if status has changed, but we only want to receive \
         //   reminders on this specific notifier channel.
@@ -50,6 +50,7 @@ impl Notifier for GotifyNotifier {
     type Error = Error;
 
     fn attempt(gotify: &Self::Config, notification: &Notification<'_>) -> Result<(), Self::Error> {
+        let conf = APP_CONF.load();
         // Build up the message text
         let mut message = String::new();
 
@@ -64,6 +65,13 @@ impl Notifier for GotifyNotifier {
             notification.status.as_str().to_uppercase()
         ));
         message.push_str(&format!("Nodes:\n{}\n", &notification.replicas.join("\n")));
+
+        let messages_lines = notification.messages_lines();
+
+        if !messages_lines.is_empty() {
+            message.push_str(&format!("Messages:\n{}\n", messages_lines.join("\n")));
+        }
+
         message.push_str(&format!("Time: {}", &notification.time));
 
         tracing::debug!("will send Gotify notification with message: {}", &message);
@@ -79,7 +87,7 @@ impl Notifier for GotifyNotifier {
         // Build message parameters
         let mut params: HashMap<&str, &str> = HashMap::new();
 
-        params.insert("title", &APP_CONF.branding.page_title);
+        params.insert("title", &conf.branding.page_title);
         params.insert("message", &message);
 
         if !notification.changed {
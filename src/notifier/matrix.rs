@@ -36,6 +36,7 @@ static MATRIX_HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
 const MATRIX_FORMATTERS: &[fn(&Notification<'_>) -> String] = &[
     format_status,
     format_replicas,
+    format_messages,
     format_status_page,
     format_time,
 ];
@@ -147,10 +148,24 @@ fn format_replicas(notification: &Notification<'_>) -> String {
     }
 }
 
+fn format_messages(notification: &Notification<'_>) -> String {
+    let messages = notification
+        .messages_lines()
+        .iter()
+        .map(|message_line| format!("<li>{}</li>", tera::escape_html(message_line)))
+        .collect::<Vec<String>>();
+
+    if messages.is_empty() {
+        "".to_string()
+    } else {
+        format!("<p>Messages:</p><ul>{}</ul>", messages.join(""))
+    }
+}
+
 fn format_status_page(_: &Notification<'_>) -> String {
     format!(
         "<p>Status page: {}</p>",
-        APP_CONF.branding.page_url.as_str()
+        APP_CONF.load().branding.page_url.as_str()
     )
 }
 
@@ -50,6 +50,7 @@ impl Notifier for PushoverNotifier {
         pushover: &Self::Config,
         notification: &Notification<'_>,
     ) -> Result<(), Self::Error> {
+        let conf = APP_CONF.load();
         // Build up the message text
         let mut message = String::new();
 
@@ -68,6 +69,14 @@ impl Notifier for PushoverNotifier {
             "<u>Nodes:</u> {}\n",
             &notification.replicas.join(", ")
         ));
+
+        for message_line in notification.messages_lines() {
+            message.push_str(&format!(
+                "<u>Message:</u> {}\n",
+                tera::escape_html(&message_line)
+            ));
+        }
+
         message.push_str(&format!("<u>Time:</u> {}", &notification.time));
 
         tracing::debug!("will send Pushover notification with message: {}", &message);
@@ -83,15 +92,15 @@ impl Notifier for PushoverNotifier {
             params.insert("user", user_key);
 
             // Append title & message
-            params.insert("title", &APP_CONF.branding.page_title);
+            params.insert("title", &conf.branding.page_title);
             params.insert("message", &message);
             params.insert("html", "1");
 
             // Append target URL
-            let url_title = format!("Details on {}", APP_CONF.branding.page_title);
+            let url_title = format!("Details on {}", conf.branding.page_title);
 
             params.insert("url_title", &url_title);
-            params.insert("url", APP_CONF.branding.page_url.as_str());
+            params.insert("url", conf.branding.page_url.as_str());
 
             // Mark as high-priority? (reminder)
             if !notification.changed {
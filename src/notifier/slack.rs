@@ -70,8 +70,10 @@ impl Notifier for SlackNotifier {
     type Error = Error;
 
     fn attempt(slack: &Self::Config, notification: &Notification<'_>) -> Result<(), Self::Error> {
+        let conf = APP_CONF.load();
         let status_label = format!("{:?}", notification.status);
         let mut nodes_label = String::new();
+        let messages_label = notification.messages_lines().join("\n");
 
         // Build message
         let message_text = if notification.startup {
@@ -116,6 +118,14 @@ impl Notifier for SlackNotifier {
             });
         }
 
+        if !messages_label.is_empty() {
+            attachment.fields.push(SlackPayloadAttachmentField {
+                title: "Messages",
+                value: &messages_label,
+                short: false,
+            });
+        }
+
         attachment.fields.push(SlackPayloadAttachmentField {
             title: "Status",
             value: &status_label,
@@ -130,7 +140,7 @@ impl Notifier for SlackNotifier {
 
         attachment.fields.push(SlackPayloadAttachmentField {
             title: "Monitor Page",
-            value: APP_CONF.branding.page_url.as_str(),
+            value: conf.branding.page_url.as_str(),
             short: false,
         });
 
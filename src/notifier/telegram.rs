@@ -111,7 +111,16 @@ impl Notifier for TelegramNotifier {
             .join("\n");
 
         message.push_str(&nodes_count_list_text);
-        message.push_str(&format!("\nLink: {}", APP_CONF.branding.page_url.as_str()));
+
+        // Notice: messages are sent as code, as they might contain characters that are \
+        //   meaningful to markdown (eg. script output)
+        for message_line in notification.messages_lines() {
+            message.push_str(&format!("\n`{}`", message_line.replace('`', "'")));
+        }
+        message.push_str(&format!(
+            "\nLink: {}",
+            APP_CONF.load().branding.page_url.as_str()
+        ));
 
         tracing::debug!("will send Telegram notification with message: {}", &message);
 
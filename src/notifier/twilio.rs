@@ -56,11 +56,16 @@ impl Notifier for TwilioNotifier {
             message.push_str("Reminder for: ");
         }
 
-        message.push_str(&format!("{}\n\n", APP_CONF.branding.page_title));
+        message.push_str(&format!("{}\n\n", APP_CONF.load().branding.page_title));
         message.push_str(&format!("Status: {:?}\n", notification.status));
         message.push_str(&format!("Nodes: {}\n", &notification.replicas.join(", ")));
         message.push_str(&format!("Time: {}\n", &notification.time));
 
+        // Notice: messages come last, as they are the first to get trimmed down if too long
+        for message_line in notification.messages_lines() {
+            message.push_str(&format!("{}\n", message_line));
+        }
+
         // Trim down message to a maximum length? (most SMS receivers and networks support \
         //   up to 1600 characters by re-building message segments)
         if message.len() > TEXT_MESSAGE_MAXIMUM_LENGTH {
@@ -88,7 +93,7 @@ impl Notifier for TwilioNotifier {
 
             // Submit message to Twilio
             let response = TWILIO_HTTP_CLIENT
-                .post(&generate_api_url(&twilio.account_sid))
+                .post(generate_api_url(&twilio.account_sid))
                 .basic_auth(
                     twilio.account_sid.as_str(),
                     Some(twilio.auth_token.as_str()),
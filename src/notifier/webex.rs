@@ -64,24 +64,32 @@ impl Notifier for WebExNotifier {
         if notification.startup {
             message.push_str(&format!(
                 "Status startup alert from: {}\n",
-                APP_CONF.branding.page_title
+                APP_CONF.load().branding.page_title
             ));
         } else if notification.changed {
             message.push_str(&format!(
                 "Status change report from: {}\n",
-                APP_CONF.branding.page_title
+                APP_CONF.load().branding.page_title
             ));
         } else {
             message.push_str(&format!(
                 "Status unchanged reminder from: {}\n",
-                APP_CONF.branding.page_title
+                APP_CONF.load().branding.page_title
             ));
         }
 
         message.push_str(&format!("Status: {:?}\n", notification.status));
         message.push_str(&format!("Nodes: {}\n", &nodes_label));
+
+        for message_line in notification.messages_lines() {
+            message.push_str(&format!("Message: {}\n", message_line));
+        }
+
         message.push_str(&format!("Time: {}\n", &notification.time));
-        message.push_str(&format!("URL: {}", APP_CONF.branding.page_url.as_str()));
+        message.push_str(&format!(
+            "URL: {}",
+            APP_CONF.load().branding.page_url.as_str()
+        ));
 
         // Build paylaod
         let payload = WebExPayload {
@@ -17,6 +17,7 @@
 
 use std::time::Duration;
 
+use indexmap::IndexMap;
 use once_cell::sync::Lazy;
 use reqwest::blocking::Client;
 use serde::Serialize;
@@ -53,6 +54,7 @@ struct WebHookPayload<'a> {
     status: &'a Status,
     time: &'a str,
     replicas: &'a [&'a str],
+    messages: IndexMap<&'a str, &'a str>,
     page: WebHookPayloadPage<'a>,
 }
 
@@ -79,6 +81,7 @@ impl Notifier for WebHookNotifier {
     type Error = Error;
 
     fn attempt(webhook: &Self::Config, notification: &Notification<'_>) -> Result<(), Self::Error> {
+        let conf = APP_CONF.load();
         // Acquire hook type
         let hook_type = if notification.startup {
             WebHookPayloadType::Startup
@@ -94,9 +97,10 @@ impl Notifier for WebHookNotifier {
             status: notification.status,
             time: notification.time.as_str(),
             replicas: &notification.replicas,
+            messages: notification.messages.iter().copied().collect(),
             page: WebHookPayloadPage {
-                title: APP_CONF.branding.page_title.as_str(),
-                url: APP_CONF.branding.page_url.as_str(),
+                title: conf.branding.page_title.as_str(),
+                url: conf.branding.page_url.as_str(),
             },
         };
 
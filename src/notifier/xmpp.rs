@@ -45,12 +45,20 @@ impl GenericNotifier for XMPPNotifier {
                 message.push_str("Reminder for: ");
             }
 
-            message.push_str(&format!("{}\n", APP_CONF.branding.page_title));
+            message.push_str(&format!("{}\n", APP_CONF.load().branding.page_title));
             message.push_str("\n");
             message.push_str(&format!("Status: {:?}\n", notification.status));
             message.push_str(&format!("Nodes: {}\n", &notification.replicas.join(", ")));
+
+            for message_line in notification.messages_lines() {
+                message.push_str(&format!("Message: {}\n", message_line));
+            }
+
             message.push_str(&format!("Time: {}\n", &notification.time));
-            message.push_str(&format!("URL: {}", APP_CONF.branding.page_url.as_str()));
+            message.push_str(&format!(
+                "URL: {}",
+                APP_CONF.load().branding.page_url.as_str()
+            ));
 
             tracing::debug!("will send XMPP notification with message: {}", &message);
 
@@ -84,11 +84,15 @@ impl Notifier for ZulipNotifier {
             message_text.push_str(&nodes_label_titled);
         }
 
+        for message_line in notification.messages_lines() {
+            message_text.push_str(&format!("\n > {}", message_line));
+        }
+
         message_text.push_str(&format!("\n **Status**: {}", &status_label));
         message_text.push_str(&format!("\n **Time**: {}", &notification.time));
         message_text.push_str(&format!(
             "\n **Page**: {}",
-            &APP_CONF.branding.page_url.as_str()
+            &APP_CONF.load().branding.page_url.as_str()
         ));
 
         // Submit payload to Zulip
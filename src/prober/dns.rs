@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::{Duration, SystemTime};
+
+use hickory_client::client::{AsyncClient, ClientHandle};
+use hickory_client::op::ResponseCode;
+use hickory_client::rr::{DNSClass, Name, RData, Record, RecordType};
+use hickory_client::udp::UdpClientStream;
+use tokio::net::{lookup_host, UdpSocket};
+
+use super::replica::DnsRecordType;
+use super::status::Status;
+
+impl From<DnsRecordType> for RecordType {
+    fn from(record_type: DnsRecordType) -> Self {
+        match record_type {
+            DnsRecordType::A => RecordType::A,
+            DnsRecordType::Aaaa => RecordType::AAAA,
+            DnsRecordType::Cname => RecordType::CNAME,
+            DnsRecordType::Mx => RecordType::MX,
+            DnsRecordType::Txt => RecordType::TXT,
+            DnsRecordType::Srv => RecordType::SRV,
+        }
+    }
+}
+
+pub async fn proceed_replica_probe_poll_dns(
+    host: &str,
+    port: u16,
+    name: &str,
+    record_type: DnsRecordType,
+    timeout: Duration,
+    expected_value: &Option<String>,
+) -> (Status, Option<Duration>) {
+    let nameserver = match lookup_host((host, port))
+        .await
+        .map(|mut address| address.next())
+    {
+        Ok(Some(nameserver)) => nameserver,
+        Ok(None) => {
+            tracing::debug!(
+                "prober poll did not resolve any address for dns nameserver: {}:{}",
+                host,
+                port
+            );
+
+            return (Status::Dead, None);
+        }
+        Err(err) => {
+            tracing::error!(
+                "prober poll address for dns nameserver is invalid: {}:{} (error: {})",
+                host,
+                port,
+                err
+            );
+
+            return (Status::Dead, None);
+        }
+    };
+
+    // Notice: the name was validated upon parsing the replica URL
+    let query_name = match Name::from_ascii(name) {
+        Ok(query_name) => query_name,
+        Err(_) => return (Status::Dead, None),
+    };
+
+    let stream = UdpClientStream::<UdpSocket>::with_timeout(nameserver, timeout);
+
+    let (mut client, background) = match AsyncClient::connect(stream).await {
+        Ok(connection) => connection,
+        Err(err) => {
+            tracing::debug!(
+                "prober poll could not connect to dns nameserver: {} (error: {})",
+                nameserver,
+                err
+            );
+
+            return (Status::Dead, None);
+        }
+    };
+
+    tracing::debug!(
+        "prober poll will fire for dns target: {} ({:?} record) on nameserver: {}",
+        name,
+        record_type,
+        nameserver
+    );
+
+    // Notice: the background task drives the exchange with the nameserver; it is aborted once \
+    //   the query completed, as the client is not reused.
+    let background = tokio::spawn(background);
+
+    // Acquire query start time (used for latency calculation)
+    let query_start_time = SystemTime::now();
+
+    let response = client
+        .query(query_name, DNSClass::IN, record_type.into())
+        .await;
+
+    background.abort();
+
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            // Notice: this includes timeouts, as well as error responses (eg. NXDOMAIN)
+            tracing::debug!(
+                "prober poll result was not received for dns target: {} (error: {})",
+                name,
+                err
+            );
+
+            return (Status::Dead, None);
+        }
+    };
+
+    let query_latency = SystemTime::now().duration_since(query_start_time).ok();
+
+    if response.response_code() != ResponseCode::NoError {
+        tracing::debug!(
+            "prober poll got error response for dns target: {} (code: {})",
+            name,
+            response.response_code()
+        );
+
+        return (Status::Dead, None);
+    }
+
+    // Acquire answered values for the queried record type (answers might include the \
+    //   intermediate records that were followed, eg. CNAME records when querying for A records)
+    let values: Vec<String> = response
+        .answers()
+        .iter()
+        .filter_map(|record| record_value(record, record_type))
+        .collect();
+
+    tracing::debug!(
+        "prober poll result received for dns target: {} with values: {:?}",
+        name,
+        values
+    );
+
+    if values.is_empty() {
+        // Consider as DOWN (the record does not exist)
+        return (Status::Dead, None);
+    }
+
+    // Check answered values for expected value? (if configured)
+    if let Some(expected_value) = expected_value {
+        let expected_value = normalize_value(expected_value, record_type);
+
+        if !values.contains(&expected_value) {
+            tracing::debug!(
+                "prober poll result for dns target: {} does not contain expected value: {}",
+                name,
+                expected_value
+            );
+
+            return (Status::Sick, query_latency);
+        }
+    }
+
+    (Status::Healthy, query_latency)
+}
+
+fn record_value(record: &Record, record_type: DnsRecordType) -> Option<String> {
+    let value = match (record.data()?, record_type) {
+        (RData::A(address), DnsRecordType::A) => address.to_string(),
+        (RData::AAAA(address), DnsRecordType::Aaaa) => address.to_string(),
+        (RData::CNAME(cname), DnsRecordType::Cname) => cname.0.to_string(),
+        (RData::MX(mx), DnsRecordType::Mx) => format!("{} {}", mx.preference(), mx.exchange()),
+        (RData::TXT(txt), DnsRecordType::Txt) => txt
+            .txt_data()
+            .iter()
+            .map(|data| String::from_utf8_lossy(data))
+            .collect(),
+        (RData::SRV(srv), DnsRecordType::Srv) => format!(
+            "{} {} {} {}",
+            srv.priority(),
+            srv.weight(),
+            srv.port(),
+            srv.target()
+        ),
+        _ => return None,
+    };
+
+    Some(normalize_value(&value, record_type))
+}
+
+fn normalize_value(value: &str, record_type: DnsRecordType) -> String {
+    // Notice: TXT values are compared as-is, while the other record values hold names, which \
+    //   are compared case-insensitively, and regardless of their trailing root dot.
+    match record_type {
+        DnsRecordType::Txt => value.to_string(),
+        _ => value.trim().trim_end_matches('.').to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use hickory_client::op::{Message, MessageType};
+    use hickory_client::rr::rdata::A;
+    use hickory_client::serialize::binary::BinDecodable;
+
+    use super::*;
+
+    // Answers each query with given A record, or with NXDOMAIN if none
+    async fn serve_nameserver(address: Option<Ipv4Addr>) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let mut buffer = [0; 512];
+
+            while let Ok((size, peer)) = socket.recv_from(&mut buffer).await {
+                let query = Message::from_bytes(&buffer[..size]).unwrap();
+                let mut response = Message::new();
+
+                response
+                    .set_id(query.id())
+                    .set_message_type(MessageType::Response)
+                    .add_queries(query.queries().to_vec());
+
+                match address {
+                    Some(address) => {
+                        response.add_answer(Record::from_rdata(
+                            query.queries()[0].name().clone(),
+                            60,
+                            RData::A(A(address)),
+                        ));
+                    }
+                    None => {
+                        response.set_response_code(ResponseCode::NXDomain);
+                    }
+                }
+
+                socket
+                    .send_to(&response.to_vec().unwrap(), peer)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        port
+    }
+
+    async fn probe(port: u16, expected_value: Option<&str>) -> Status {
+        proceed_replica_probe_poll_dns(
+            "127.0.0.1",
+            port,
+            "example.com.",
+            DnsRecordType::A,
+            Duration::from_secs(2),
+            &expected_value.map(str::to_string),
+        )
+        .await
+        .0
+    }
+
+    #[tokio::test]
+    async fn answered_record() {
+        let port = serve_nameserver(Some(Ipv4Addr::new(192, 0, 2, 1))).await;
+
+        assert_eq!(probe(port, None).await, Status::Healthy);
+        assert_eq!(probe(port, Some("192.0.2.1")).await, Status::Healthy);
+        assert_eq!(probe(port, Some("192.0.2.2")).await, Status::Sick);
+    }
+
+    #[tokio::test]
+    async fn missing_record() {
+        let port = serve_nameserver(None).await;
+
+        assert_eq!(probe(port, None).await, Status::Dead);
+    }
+
+    #[tokio::test]
+    async fn unresponsive_nameserver() {
+        // Notice: nothing answers on this socket, as it is never read from
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        assert_eq!(probe(port, None).await, Status::Dead);
+    }
+}
@@ -0,0 +1,539 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, TE};
+use http::{Method, Request, StatusCode};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout as with_timeout;
+
+use super::http::make_default_headers;
+use super::status::Status;
+use super::tls::{self, TlsOptions};
+
+const GRPC_HEALTH_CHECK_PATH: &str = "/grpc.health.v1.Health/Check";
+const GRPC_MESSAGE_PREFIX_SIZE: usize = 5;
+const GRPC_MESSAGE_MAXIMUM_SIZE: usize = 16384;
+const GRPC_ALPN_PROTOCOL: &[u8] = b"h2";
+
+static GRPC_STATUS: HeaderName = HeaderName::from_static("grpc-status");
+static GRPC_MESSAGE: HeaderName = HeaderName::from_static("grpc-message");
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("could not resolve any address")]
+    NoAddress,
+
+    #[error("connection failed: {0}")]
+    Connect(#[source] io::Error),
+
+    #[error(transparent)]
+    Tls(#[from] tls::Error),
+
+    #[error("invalid request: {0}")]
+    Request(#[from] http::Error),
+
+    #[error("http/2 error: {0}")]
+    Http2(#[from] h2::Error),
+
+    #[error("unexpected http status: {0}")]
+    HttpStatus(StatusCode),
+
+    #[error("grpc call failed with status: {0} ({1})")]
+    Call(u32, String),
+
+    #[error("invalid response message")]
+    InvalidMessage,
+}
+
+/// Serving status, as defined by the 'grpc.health.v1' protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ServingStatus {
+    Unknown,
+    Serving,
+    NotServing,
+    ServiceUnknown,
+}
+
+impl ServingStatus {
+    // Severity of the status, used to pick the worst status of all probed addresses
+    fn severity(&self) -> u8 {
+        match self {
+            ServingStatus::Serving => 0,
+            ServingStatus::Unknown => 1,
+            ServingStatus::NotServing | ServingStatus::ServiceUnknown => 2,
+        }
+    }
+}
+
+pub async fn proceed_replica_probe_poll_grpc(
+    host: &str,
+    port: u16,
+    service: &str,
+    secure: bool,
+    timeout: Duration,
+    headers: &HeaderMap,
+    tls_options: &TlsOptions,
+) -> (Status, Option<Duration>) {
+    tracing::debug!(
+        "prober poll will fire for grpc target: {}:{} with service: '{}'",
+        host,
+        port,
+        service
+    );
+
+    // Acquire check start time (used for latency calculation, which includes connecting)
+    let check_start_time = SystemTime::now();
+
+    let serving_status = match with_timeout(
+        timeout,
+        check_health(host, port, service, secure, timeout, headers, tls_options),
+    )
+    .await
+    {
+        Ok(Ok(serving_status)) => serving_status,
+        Ok(Err(err)) => {
+            tracing::debug!(
+                "prober poll error for grpc target: {}:{} (error: {})",
+                host,
+                port,
+                err
+            );
+
+            return (Status::Dead, None);
+        }
+        Err(_) => {
+            tracing::debug!("prober poll timed out for grpc target: {}:{}", host, port);
+
+            return (Status::Dead, None);
+        }
+    };
+
+    let check_latency = SystemTime::now().duration_since(check_start_time).ok();
+
+    tracing::debug!(
+        "prober poll result received for grpc target: {}:{} with serving status: {:?}",
+        host,
+        port,
+        serving_status
+    );
+
+    match serving_status {
+        ServingStatus::Serving => (Status::Healthy, check_latency),
+        ServingStatus::Unknown => (Status::Sick, check_latency),
+        ServingStatus::NotServing | ServingStatus::ServiceUnknown => (Status::Dead, None),
+    }
+}
+
+async fn check_health(
+    host: &str,
+    port: u16,
+    service: &str,
+    secure: bool,
+    timeout: Duration,
+    headers: &HeaderMap,
+    tls_options: &TlsOptions,
+) -> Result<ServingStatus, Error> {
+    let addresses: Vec<SocketAddr> = lookup_host((host, port))
+        .await
+        .map_err(Error::Connect)?
+        .collect();
+
+    // Notice: as for TCP and UDP probes, all resolved addresses are checked, as a single \
+    //   unhealthy address is sufficient for the whole replica to be considered unhealthy (the \
+    //   worst serving status wins, and the timeout covers checking all addresses).
+    let mut serving_status: Option<ServingStatus> = None;
+
+    for address in addresses {
+        let address_status = check_health_address(
+            address,
+            host,
+            port,
+            service,
+            secure,
+            timeout,
+            headers,
+            tls_options,
+        )
+        .await?;
+
+        tracing::debug!(
+            "prober poll got grpc serving status: {:?} from address: {}",
+            address_status,
+            address
+        );
+
+        serving_status = match serving_status {
+            Some(serving_status) if serving_status.severity() >= address_status.severity() => {
+                Some(serving_status)
+            }
+            _ => Some(address_status),
+        };
+    }
+
+    serving_status.ok_or(Error::NoAddress)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn check_health_address(
+    address: SocketAddr,
+    host: &str,
+    port: u16,
+    service: &str,
+    secure: bool,
+    timeout: Duration,
+    headers: &HeaderMap,
+    tls_options: &TlsOptions,
+) -> Result<ServingStatus, Error> {
+    let stream = TcpStream::connect(address).await.map_err(Error::Connect)?;
+
+    let authority = format!("{}:{}", host, port);
+
+    if secure {
+        let stream =
+            tls::handshake(stream, host, timeout, tls_options, &[GRPC_ALPN_PROTOCOL]).await?;
+
+        call_health_check(stream, "https", &authority, service, headers).await
+    } else {
+        call_health_check(stream, "http", &authority, service, headers).await
+    }
+}
+
+async fn call_health_check<S>(
+    stream: S,
+    scheme: &str,
+    authority: &str,
+    service: &str,
+    headers: &HeaderMap,
+) -> Result<ServingStatus, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (client, connection) = h2::client::handshake(stream).await?;
+
+    // Notice: the connection drives the HTTP/2 exchange, thus it must be polled aside; it ends \
+    //   once the client and its streams are dropped.
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            tracing::debug!("prober poll grpc connection closed (error: {})", err);
+        }
+    });
+
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "{}://{}{}",
+            scheme, authority, GRPC_HEALTH_CHECK_PATH
+        ))
+        .header(CONTENT_TYPE, HeaderValue::from_static("application/grpc"))
+        .header(TE, HeaderValue::from_static("trailers"))
+        .body(())?;
+
+    request.headers_mut().extend(make_default_headers());
+
+    // Node headers are sent as call metadata (eg. for authentication)
+    request.headers_mut().extend(headers.clone());
+
+    let mut client = client.ready().await?;
+    let (response, mut send_stream) = client.send_request(request, false)?;
+
+    send_stream.send_data(encode_request(service), true)?;
+
+    let response = response.await?;
+
+    if response.status() != StatusCode::OK {
+        return Err(Error::HttpStatus(response.status()));
+    }
+
+    // Call failed straight away? (ie. 'Trailers-Only' response, where the status is in headers)
+    check_call_status(response.headers())?;
+
+    let mut body = response.into_body();
+    let mut message = Vec::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+
+        let _ = body.flow_control().release_capacity(chunk.len());
+
+        message.extend_from_slice(&chunk);
+
+        if message.len() > GRPC_MESSAGE_MAXIMUM_SIZE {
+            return Err(Error::InvalidMessage);
+        }
+    }
+
+    // Check call status (as returned in trailers, following the response message)
+    if let Some(trailers) = body.trailers().await? {
+        check_call_status(&trailers)?;
+    }
+
+    decode_response(&message)
+}
+
+fn check_call_status(headers: &HeaderMap) -> Result<(), Error> {
+    let code = match headers.get(&GRPC_STATUS) {
+        Some(code) => code
+            .to_str()
+            .ok()
+            .and_then(|code| code.parse::<u32>().ok())
+            .ok_or(Error::InvalidMessage)?,
+        None => return Ok(()),
+    };
+
+    if code == 0 {
+        return Ok(());
+    }
+
+    let message = headers
+        .get(&GRPC_MESSAGE)
+        .map(|message| String::from_utf8_lossy(message.as_bytes()).into_owned())
+        .unwrap_or_default();
+
+    Err(Error::Call(code, message))
+}
+
+fn encode_request(service: &str) -> Bytes {
+    // Notice: the 'HealthCheckRequest' message has a single 'service' string field (#1), which \
+    //   is omitted if empty (as for any default value in proto3).
+    let mut message = Vec::new();
+
+    if !service.is_empty() {
+        message.push(0x0a);
+
+        encode_varint(service.len() as u64, &mut message);
+
+        message.extend_from_slice(service.as_bytes());
+    }
+
+    // Prefix message with its compression flag (not compressed) and length
+    let mut frame = Vec::with_capacity(GRPC_MESSAGE_PREFIX_SIZE + message.len());
+
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&message);
+
+    Bytes::from(frame)
+}
+
+fn decode_response(frame: &[u8]) -> Result<ServingStatus, Error> {
+    if frame.len() < GRPC_MESSAGE_PREFIX_SIZE || frame[0] != 0 {
+        return Err(Error::InvalidMessage);
+    }
+
+    let size = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]) as usize;
+
+    if size > GRPC_MESSAGE_MAXIMUM_SIZE {
+        return Err(Error::InvalidMessage);
+    }
+
+    let mut message = frame
+        .get(GRPC_MESSAGE_PREFIX_SIZE..GRPC_MESSAGE_PREFIX_SIZE + size)
+        .ok_or(Error::InvalidMessage)?;
+
+    // Notice: the 'HealthCheckResponse' message has a single 'status' enum field (#1), which \
+    //   defaults to 'UNKNOWN' if omitted; any other field is skipped.
+    let mut status = 0;
+
+    while !message.is_empty() {
+        let key = decode_varint(&mut message)?;
+
+        match (key >> 3, key & 0x07) {
+            (1, 0) => status = decode_varint(&mut message)?,
+            (_, 0) => {
+                decode_varint(&mut message)?;
+            }
+            (_, 1) => message = message.get(8..).ok_or(Error::InvalidMessage)?,
+            (_, 2) => {
+                let length = decode_varint(&mut message)? as usize;
+
+                message = message.get(length..).ok_or(Error::InvalidMessage)?;
+            }
+            (_, 5) => message = message.get(4..).ok_or(Error::InvalidMessage)?,
+            _ => return Err(Error::InvalidMessage),
+        }
+    }
+
+    Ok(match status {
+        1 => ServingStatus::Serving,
+        2 => ServingStatus::NotServing,
+        3 => ServingStatus::ServiceUnknown,
+        _ => ServingStatus::Unknown,
+    })
+}
+
+fn encode_varint(mut value: u64, buffer: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buffer.push((value as u8 & 0x7f) | 0x80);
+
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+fn decode_varint(buffer: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = buffer.split_first().ok_or(Error::InvalidMessage)?;
+
+        *buffer = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(Error::InvalidMessage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Prefixes a 'HealthCheckResponse' message, as done by gRPC servers
+    fn frame(message: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0];
+
+        frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        frame.extend_from_slice(message);
+
+        frame
+    }
+
+    #[test]
+    fn encode_request_service() {
+        assert_eq!(&encode_request("")[..], &[0, 0, 0, 0, 0]);
+
+        assert_eq!(
+            &encode_request("jobs")[..],
+            &[0, 0, 0, 0, 6, 0x0a, 4, b'j', b'o', b'b', b's']
+        );
+
+        // Service names longer than 127 bytes get a multi-byte length
+        let request = encode_request(&"a".repeat(200));
+
+        assert_eq!(&request[..8], &[0, 0, 0, 0, 203, 0x0a, 0xc8, 0x01]);
+        assert_eq!(request.len(), GRPC_MESSAGE_PREFIX_SIZE + 203);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, 16384, u32::MAX as u64, u64::MAX] {
+            let mut buffer = Vec::new();
+
+            encode_varint(value, &mut buffer);
+
+            let mut reader = &buffer[..];
+
+            assert_eq!(decode_varint(&mut reader).unwrap(), value);
+            assert!(reader.is_empty());
+        }
+
+        assert_eq!(
+            {
+                let mut buffer = Vec::new();
+                encode_varint(300, &mut buffer);
+                buffer
+            },
+            [0xac, 0x02]
+        );
+    }
+
+    #[test]
+    fn varint_truncated_or_overlong() {
+        assert!(decode_varint(&mut &[][..]).is_err());
+        assert!(decode_varint(&mut &[0x80][..]).is_err());
+        assert!(decode_varint(&mut &[0xff; 11][..]).is_err());
+    }
+
+    #[test]
+    fn decode_serving_status() {
+        assert_eq!(
+            decode_response(&frame(&[0x08, 1])).unwrap(),
+            ServingStatus::Serving
+        );
+        assert_eq!(
+            decode_response(&frame(&[0x08, 2])).unwrap(),
+            ServingStatus::NotServing
+        );
+        assert_eq!(
+            decode_response(&frame(&[0x08, 3])).unwrap(),
+            ServingStatus::ServiceUnknown
+        );
+
+        // Omitted status defaults to 'UNKNOWN', as do unknown status values
+        assert_eq!(
+            decode_response(&frame(&[])).unwrap(),
+            ServingStatus::Unknown
+        );
+        assert_eq!(
+            decode_response(&frame(&[0x08, 9])).unwrap(),
+            ServingStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn decode_skips_unknown_fields() {
+        let message = [
+            // Field #2 (varint)
+            0x10, 0x96, 0x01, //
+            // Field #3 (length-delimited)
+            0x1a, 3, b'a', b'b', b'c', //
+            // Field #4 (fixed 64 bits)
+            0x21, 1, 2, 3, 4, 5, 6, 7, 8, //
+            // Field #5 (fixed 32 bits)
+            0x2d, 1, 2, 3, 4, //
+            // Field #1 (status)
+            0x08, 1,
+        ];
+
+        assert_eq!(
+            decode_response(&frame(&message)).unwrap(),
+            ServingStatus::Serving
+        );
+    }
+
+    #[test]
+    fn decode_invalid_frames() {
+        // Truncated prefix
+        assert!(decode_response(&[0, 0, 0]).is_err());
+
+        // Truncated message (declared length is larger than the message)
+        let mut truncated = frame(&[0x08, 1]);
+        truncated.pop();
+
+        assert!(decode_response(&truncated).is_err());
+
+        // Oversized message
+        let mut oversized = vec![0];
+        oversized.extend_from_slice(&u32::MAX.to_be_bytes());
+
+        assert!(decode_response(&oversized).is_err());
+
+        // Compressed message
+        let mut compressed = frame(&[0x08, 1]);
+        compressed[0] = 1;
+
+        assert!(decode_response(&compressed).is_err());
+
+        // Unsupported wire type (start group), and fields cut short
+        assert!(decode_response(&frame(&[0x0b])).is_err());
+        assert!(decode_response(&frame(&[0x08])).is_err());
+        assert!(decode_response(&frame(&[0x1a, 5, b'a'])).is_err());
+        assert!(decode_response(&frame(&[0x21, 1, 2])).is_err());
+    }
+}
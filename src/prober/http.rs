@@ -0,0 +1,134 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use reqwest::header::{HeaderMap, USER_AGENT};
+use reqwest::redirect::Policy as RedirectPolicy;
+use reqwest::{Certificate, Client, Identity, Proxy};
+use url::Url;
+
+use super::tls::TlsOptions;
+use crate::APP_CONF;
+
+const REDIRECTS_MAXIMUM_DEFAULT: usize = 10;
+
+static PROBE_HTTP_CLIENTS: Lazy<RwLock<HashMap<HttpClientOptions, Client>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// HTTP client options for a node; nodes sharing the same options share the same client.
+#[derive(Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub struct HttpClientOptions {
+    pub timeout: Option<u64>,
+    pub follow_redirects: bool,
+    pub max_redirects: Option<usize>,
+    pub proxy: Option<Url>,
+    pub tls: TlsOptions,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("could not read {0:?}: {1}")]
+    Read(PathBuf, #[source] io::Error),
+
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+/// Returns the HTTP client for given options, building it upon first use.
+pub fn acquire_client(options: &HttpClientOptions) -> Result<Client, Error> {
+    if let Some(client) = PROBE_HTTP_CLIENTS.read().get(options) {
+        return Ok(client.clone());
+    }
+
+    tracing::debug!("building http client for options: {:?}", options);
+
+    let client = make_client(options)?;
+
+    PROBE_HTTP_CLIENTS
+        .write()
+        .insert(options.clone(), client.clone());
+
+    Ok(client)
+}
+
+/// Clears cached clients, so that they get built again from the current configuration.
+pub fn clear_clients() {
+    PROBE_HTTP_CLIENTS.write().clear();
+}
+
+fn make_client(options: &HttpClientOptions) -> Result<Client, Error> {
+    // Notice: the timeout defaults to the dead delay, which is read when the client gets built; \
+    //   this is why clients are cleared upon reloading the configuration.
+    let timeout = options
+        .timeout
+        .unwrap_or(APP_CONF.load().metrics.poll_delay_dead);
+
+    let redirect_policy = if options.follow_redirects {
+        RedirectPolicy::limited(options.max_redirects.unwrap_or(REDIRECTS_MAXIMUM_DEFAULT))
+    } else {
+        RedirectPolicy::none()
+    };
+
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .gzip(false)
+        .redirect(redirect_policy)
+        .default_headers(make_default_headers())
+        .danger_accept_invalid_certs(options.tls.insecure);
+
+    if let Some(ref proxy) = options.proxy {
+        builder = builder.proxy(Proxy::all(proxy.as_str())?);
+    }
+
+    // Trust the custom CA bundle if any (in place of the well-known roots)
+    if let Some(ref ca_path) = options.tls.ca_path {
+        builder = builder
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(Certificate::from_pem(&read_file(ca_path)?)?);
+    }
+
+    // Present a client certificate? (for mutual TLS)
+    if let (Some(certificate_path), Some(key_path)) = (
+        &options.tls.client_certificate_path,
+        &options.tls.client_key_path,
+    ) {
+        let mut identity = read_file(certificate_path)?;
+
+        identity.push(b'\n');
+        identity.extend(read_file(key_path)?);
+
+        builder = builder.identity(Identity::from_pem(&identity)?);
+    }
+
+    Ok(builder.build()?)
+}
+
+pub fn make_default_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        USER_AGENT,
+        format!("vigil (+{})", APP_CONF.load().branding.page_url.as_str())
+            .parse()
+            .unwrap(),
+    );
+
+    headers
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|err| Error::Read(path.to_path_buf(), err))
+}
@@ -0,0 +1,404 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::cmp::min;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use rand::random;
+
+use super::states::ServiceStatesProbeNodeReplicaMetricsIcmp;
+use super::status::Status;
+use crate::util::ping::ping;
+use crate::APP_CONF;
+
+const PROBE_ICMP_TIMEOUT_SECONDS: u64 = 1;
+
+/// ICMP options for a node; unset options fall back to the global `metrics` values.
+#[derive(Clone, Default, Debug)]
+pub struct IcmpOptions {
+    pub count: Option<u16>,
+    pub interval: Option<u64>,
+    pub loss_sick_above: Option<u8>,
+    pub loss_dead_above: Option<u8>,
+    pub rtt_sick_above: Option<u64>,
+    pub rtt_dead_above: Option<u64>,
+}
+
+pub fn proceed_replica_probe_poll_icmp(
+    host: &str,
+    timeout: Duration,
+    options: &IcmpOptions,
+) -> (
+    Status,
+    Option<Duration>,
+    Option<ServiceStatesProbeNodeReplicaMetricsIcmp>,
+) {
+    // Notice: a dummy port of value '0' is set here, so that we can resolve the host to an actual
+    //   IP address using the standard library, which avoids depending on an additional library.
+    let address_values: Vec<SocketAddr> = match (host, 0).to_socket_addrs() {
+        Ok(address) => address.collect(),
+        Err(err) => {
+            tracing::error!(
+                "prober poll address for icmp replica is invalid: {} (error: {})",
+                host,
+                err
+            );
+
+            // Consider invalid URL as a failure
+            return (Status::Dead, None, None);
+        }
+    };
+
+    if address_values.is_empty() {
+        tracing::debug!(
+            "prober poll did not resolve any address for icmp replica: {}",
+            host
+        );
+
+        // Consider empty as a failure
+        return (Status::Dead, None, None);
+    }
+
+    // Notice: the ICMP probe checker is a bit special, in the sense that it checks all \
+    //   resolved addresses. As we check for an host health at the IP level (ie. not at \
+    //   the application layer level), checking only the first host in the list is not \
+    //   sufficient for the whole replica group to be up. This can be used as an handy way \
+    //   to check for the health of a group of IP hosts, configured in a single DNS record.
+    tracing::debug!(
+        "prober poll will fire for icmp host: {} ({} targets)",
+        host,
+        address_values.len()
+    );
+
+    // Storage variable for the worst metrics found for probed addresses (ie. the highest packet \
+    //   loss, then the highest average round-trip-time)
+    let mut worst_metrics: Option<ServiceStatesProbeNodeReplicaMetricsIcmp> = None;
+
+    // Probe all returned addresses (sequentially)
+    for address_value in &address_values {
+        let metrics =
+            proceed_replica_probe_poll_icmp_address(address_value.ip(), host, timeout, options);
+
+        tracing::debug!(
+            "prober poll for icmp target: {} from host: {} lost {}% packets (average rtt: {:?}ms)",
+            address_value.ip(),
+            host,
+            metrics.loss,
+            metrics.rtt_average
+        );
+
+        let is_worse = worst_metrics.as_ref().is_none_or(|worst_metrics| {
+            (metrics.loss, metrics.rtt_average) > (worst_metrics.loss, worst_metrics.rtt_average)
+        });
+
+        if is_worse {
+            worst_metrics = Some(metrics);
+        }
+    }
+
+    match worst_metrics {
+        Some(metrics) => {
+            let (status, rtt) = classify(&metrics, options);
+
+            (status, rtt, Some(metrics))
+        }
+        None => (Status::Dead, None, None),
+    }
+}
+
+fn proceed_replica_probe_poll_icmp_address(
+    address_ip: IpAddr,
+    host: &str,
+    timeout: Duration,
+    options: &IcmpOptions,
+) -> ServiceStatesProbeNodeReplicaMetricsIcmp {
+    let metrics_conf = &APP_CONF.load().metrics;
+
+    let count = options.count.unwrap_or(metrics_conf.poll_icmp_count).max(1);
+    let interval =
+        Duration::from_millis(options.interval.unwrap_or(metrics_conf.poll_icmp_interval));
+
+    let socket_type = APP_CONF
+        .load()
+        .plugins
+        .icmp
+        .as_ref()
+        .map(|x| x.socket_type)
+        .unwrap_or_default();
+
+    // As ICMP pings require a lower-than-usual timeout, an hard-coded ICMP \
+    //   timeout value is used by default, though the configured dead delay value \
+    //   is preferred in the event it is lower than the hard-coded value (unlikely \
+    //   though possible in some setups).
+    let pinger_timeout = min(Duration::from_secs(PROBE_ICMP_TIMEOUT_SECONDS), timeout);
+
+    // Notice: all pings in a burst share the same identifier, while their sequence number is \
+    //   incremented for each ping.
+    let ident = random();
+    let mut rtts = Vec::with_capacity(count as usize);
+
+    for sequence in 1..=count {
+        // Hold on between pings (not before the first one)
+        if sequence > 1 {
+            thread::sleep(interval);
+        }
+
+        tracing::debug!(
+            "prober poll will send icmp ping #{} to target: {} from host: {}",
+            sequence,
+            address_ip,
+            host
+        );
+
+        // Acquire ping start time (used for RTT calculation)
+        let ping_start_time = SystemTime::now();
+
+        // Ping target IP address
+        match ping(
+            address_ip,
+            Some(pinger_timeout),
+            None,
+            Some(ident),
+            Some(sequence),
+            None,
+            socket2::Type::from(socket_type),
+        ) {
+            Ok(_) => {
+                rtts.push(
+                    SystemTime::now()
+                        .duration_since(ping_start_time)
+                        .unwrap_or(Duration::from_secs(0)),
+                );
+            }
+            Err(err) => {
+                tracing::debug!(
+                    "prober poll error for icmp ping #{} to target: {} from host: {} (error: {})",
+                    sequence,
+                    address_ip,
+                    host,
+                    err
+                );
+            }
+        }
+    }
+
+    make_metrics(count, &rtts)
+}
+
+fn make_metrics(sent: u16, rtts: &[Duration]) -> ServiceStatesProbeNodeReplicaMetricsIcmp {
+    let received = rtts.len() as u16;
+    let loss = (((sent - received) as f64 / sent as f64) * 100.0).round() as u8;
+
+    let rtts_milliseconds: Vec<f64> = rtts.iter().map(|rtt| rtt.as_secs_f64() * 1000.0).collect();
+
+    let (rtt_minimum, rtt_average, rtt_maximum, jitter) = if rtts_milliseconds.is_empty() {
+        (None, None, None, None)
+    } else {
+        let sum: f64 = rtts_milliseconds.iter().sum();
+
+        // Notice: jitter is the mean deviation between consecutive round-trip-times (as in \
+        //   RFC 3550), which requires at least two replies.
+        let jitter = if rtts_milliseconds.len() > 1 {
+            let deviations: f64 = rtts_milliseconds
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]).abs())
+                .sum();
+
+            Some(round_milliseconds(
+                deviations / (rtts_milliseconds.len() - 1) as f64,
+            ))
+        } else {
+            None
+        };
+
+        (
+            rtts_milliseconds
+                .iter()
+                .copied()
+                .reduce(f64::min)
+                .map(round_milliseconds),
+            Some(round_milliseconds(sum / rtts_milliseconds.len() as f64)),
+            rtts_milliseconds
+                .iter()
+                .copied()
+                .reduce(f64::max)
+                .map(round_milliseconds),
+            jitter,
+        )
+    };
+
+    ServiceStatesProbeNodeReplicaMetricsIcmp {
+        sent,
+        received,
+        loss,
+        rtt_minimum,
+        rtt_average,
+        rtt_maximum,
+        jitter,
+    }
+}
+
+fn classify(
+    metrics: &ServiceStatesProbeNodeReplicaMetricsIcmp,
+    options: &IcmpOptions,
+) -> (Status, Option<Duration>) {
+    let metrics_conf = &APP_CONF.load().metrics;
+
+    let loss_sick_above = options
+        .loss_sick_above
+        .unwrap_or(metrics_conf.poll_icmp_loss_sick_above);
+    let loss_dead_above = options
+        .loss_dead_above
+        .unwrap_or(metrics_conf.poll_icmp_loss_dead_above);
+
+    // Notice: the average round-trip-time is reported as the replica latency, which also gets \
+    //   checked against the usual sick delay.
+    let rtt = metrics
+        .rtt_average
+        .map(|rtt_average| Duration::from_secs_f64(rtt_average / 1000.0));
+
+    // No reply at all? Consider as DOWN.
+    if metrics.received == 0 || metrics.loss > loss_dead_above {
+        return (Status::Dead, None);
+    }
+
+    let rtt_average = metrics.rtt_average.unwrap_or_default();
+
+    if options
+        .rtt_dead_above
+        .is_some_and(|rtt_dead_above| rtt_average > rtt_dead_above as f64)
+    {
+        return (Status::Dead, None);
+    }
+
+    if metrics.loss > loss_sick_above
+        || options
+            .rtt_sick_above
+            .is_some_and(|rtt_sick_above| rtt_average > rtt_sick_above as f64)
+    {
+        return (Status::Sick, rtt);
+    }
+
+    (Status::Healthy, rtt)
+}
+
+fn round_milliseconds(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn milliseconds(values: &[u64]) -> Vec<Duration> {
+        values.iter().copied().map(Duration::from_millis).collect()
+    }
+
+    fn options(loss_sick_above: u8, loss_dead_above: u8) -> IcmpOptions {
+        IcmpOptions {
+            loss_sick_above: Some(loss_sick_above),
+            loss_dead_above: Some(loss_dead_above),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn metrics_total_loss() {
+        let metrics = make_metrics(4, &[]);
+
+        assert_eq!((metrics.sent, metrics.received, metrics.loss), (4, 0, 100));
+        assert_eq!(metrics.rtt_minimum, None);
+        assert_eq!(metrics.rtt_average, None);
+        assert_eq!(metrics.rtt_maximum, None);
+        assert_eq!(metrics.jitter, None);
+
+        // Even if loss was tolerated, no reply at all is dead
+        assert_eq!(classify(&metrics, &options(100, 100)), (Status::Dead, None));
+    }
+
+    #[test]
+    fn metrics_single_reply() {
+        let metrics = make_metrics(3, &milliseconds(&[20]));
+
+        assert_eq!((metrics.sent, metrics.received, metrics.loss), (3, 1, 67));
+        assert_eq!(metrics.rtt_minimum, Some(20.0));
+        assert_eq!(metrics.rtt_average, Some(20.0));
+        assert_eq!(metrics.rtt_maximum, Some(20.0));
+
+        // Jitter requires at least two replies
+        assert_eq!(metrics.jitter, None);
+    }
+
+    #[test]
+    fn metrics_jitter() {
+        // Deviations between consecutive replies are 10, 30 and 5 (ie. a mean of 15)
+        let metrics = make_metrics(4, &milliseconds(&[20, 30, 0, 5]));
+
+        assert_eq!(metrics.loss, 0);
+        assert_eq!(metrics.rtt_minimum, Some(0.0));
+        assert_eq!(metrics.rtt_average, Some(13.75));
+        assert_eq!(metrics.rtt_maximum, Some(30.0));
+        assert_eq!(metrics.jitter, Some(15.0));
+
+        // Steady replies have no jitter, and values are rounded to the microsecond
+        let metrics = make_metrics(2, &[Duration::from_nanos(1_000_400); 2]);
+
+        assert_eq!(metrics.rtt_average, Some(1.0));
+        assert_eq!(metrics.jitter, Some(0.0));
+    }
+
+    #[test]
+    fn classify_loss_thresholds() {
+        let rtts = milliseconds(&[10, 10]);
+
+        // Thresholds are exclusive (loss must be above them)
+        assert_eq!(
+            classify(&make_metrics(4, &rtts), &options(50, 75)),
+            (Status::Healthy, Some(Duration::from_millis(10)))
+        );
+        assert_eq!(
+            classify(&make_metrics(4, &rtts), &options(25, 75)),
+            (Status::Sick, Some(Duration::from_millis(10)))
+        );
+        assert_eq!(
+            classify(&make_metrics(4, &rtts), &options(25, 40)),
+            (Status::Dead, None)
+        );
+    }
+
+    #[test]
+    fn classify_rtt_thresholds() {
+        let metrics = make_metrics(2, &milliseconds(&[100, 200]));
+
+        let rtt_options = |rtt_sick_above, rtt_dead_above| IcmpOptions {
+            rtt_sick_above,
+            rtt_dead_above,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            classify(&metrics, &rtt_options(Some(150), Some(300))),
+            (Status::Healthy, Some(Duration::from_millis(150)))
+        );
+        assert_eq!(
+            classify(&metrics, &rtt_options(Some(100), Some(300))),
+            (Status::Sick, Some(Duration::from_millis(150)))
+        );
+        assert_eq!(
+            classify(&metrics, &rtt_options(Some(100), Some(120))),
+            (Status::Dead, None)
+        );
+        assert_eq!(
+            classify(&metrics, &rtt_options(None, None)),
+            (Status::Healthy, Some(Duration::from_millis(150)))
+        );
+    }
+}
@@ -15,37 +15,50 @@
 // Copyright: 2018, Valerian Saliou <valerian@valeriansaliou.name>
 // License: Mozilla Public License v2.0 (MPL v2.0)
 
-use std::cmp::min;
-use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use indexmap::IndexMap;
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use regex::Regex;
-use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, USER_AGENT};
-use reqwest::redirect::Policy as RedirectPolicy;
-use run_script::{self, ScriptOptions};
+use reqwest::header::HeaderMap;
 use time;
-
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task;
+use tokio::time::{sleep, timeout as with_timeout};
+
+use super::dns::proceed_replica_probe_poll_dns;
+use super::grpc::proceed_replica_probe_poll_grpc;
+use super::http::{acquire_client, clear_clients, HttpClientOptions};
+use super::icmp::{proceed_replica_probe_poll_icmp, IcmpOptions};
+use super::protocol::proceed_replica_probe_poll_protocol;
 use super::replica::ReplicaUrl;
+use super::schedule::{Schedule, ScheduleOptions};
+use super::script::proceed_replica_probe_script;
+use super::sql::{proceed_replica_probe_poll_sql, SqlOptions};
 use super::states::{
     ServiceStates, ServiceStatesNotifier, ServiceStatesProbe, ServiceStatesProbeNode,
     ServiceStatesProbeNodeRabbitMQ, ServiceStatesProbeNodeReplica,
-    ServiceStatesProbeNodeReplicaMetrics,
+    ServiceStatesProbeNodeReplicaMetrics, ServiceStatesProbeNodeReplicaMetricsTls,
 };
 use super::status::Status;
-use crate::config::probe::HttpMethod;
+use super::tls::{clear_client_configs, fetch_certificate, TlsOptions};
+use super::udp::{proceed_replica_probe_poll_udp, UdpOptions};
+use super::websocket::{proceed_replica_probe_poll_websocket, WebSocketOptions};
+use crate::config::probe::{HttpBodyJsonAssertion, HttpMethod, Service, ServiceNode};
+use crate::config::Config;
 use crate::prober::manager::STORE as PROBER_STORE;
 use crate::prober::mode::Mode;
-use crate::util::ping::ping;
 use crate::APP_CONF;
 
-const PROBE_HOLD_MILLISECONDS: u64 = 500;
-const PROBE_ICMP_TIMEOUT_SECONDS: u64 = 1;
+const SCHEDULE_WAIT_MAXIMUM_MILLISECONDS: u64 = 1000;
+const PROBE_TCP_RESPONSE_MAXIMUM_BYTES: usize = 16384;
 
 pub static STORE: Lazy<Arc<RwLock<Store>>> = Lazy::new(|| {
     Arc::new(RwLock::new(Store {
@@ -56,28 +69,35 @@ pub static STORE: Lazy<Arc<RwLock<Store>>> = Lazy::new(|| {
             notifier: ServiceStatesNotifier {
                 reminder_backoff_counter: 1,
                 reminder_ignore_until: None,
+                acknowledgement: None,
             },
         },
         notified: None,
+        generation: 0,
     }))
 });
 
-static PROBE_HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
-    Client::builder()
-        .timeout(Duration::from_secs(APP_CONF.metrics.poll_delay_dead))
-        .gzip(false)
-        .redirect(RedirectPolicy::none())
-        .default_headers(make_default_headers())
-        .build()
-        .unwrap()
-});
-
 pub struct Store {
     pub states: ServiceStates,
     pub notified: Option<SystemTime>,
+
+    // Bumped whenever probes get (re)initialized, so that schedulers know when to re-map replicas
+    pub generation: u64,
 }
 
-#[derive(Clone)]
+#[derive(Debug, thiserror::Error)]
+pub enum InitializeError {
+    #[error("non-poll node cannot have replicas: {0}:{1}")]
+    ReplicasNotPoll(String, String),
+
+    #[error("non-script node cannot have scripts: {0}:{1}")]
+    ScriptsNotScript(String, String),
+
+    #[error("invalid replica url: {0}:{1}:{2}")]
+    InvalidReplicaUrl(String, String, String),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct ProbeReplicaTarget {
     pub probe_id: String,
     pub node_id: String,
@@ -87,43 +107,53 @@ struct ProbeReplicaTarget {
 #[derive(Clone)]
 struct ProbeReplicaPoll {
     pub replica_url: ReplicaUrl,
+    pub schedule: ScheduleOptions,
     pub http_cache_buster: bool,
     pub http_headers: HeaderMap,
+    pub http_client: HttpClientOptions,
     pub http_method: Option<HttpMethod>,
     pub http_body: Option<String>,
+    pub http_status_healthy_above: Option<u16>,
+    pub http_status_healthy_below: Option<u16>,
+    pub http_headers_match: IndexMap<String, Regex>,
     pub body_match: Option<Regex>,
+    pub body_unmatch: Option<Regex>,
+    pub body_json_assertions: Vec<HttpBodyJsonAssertion>,
+    pub tcp_send: Option<String>,
+    pub tcp_response_match: Option<Regex>,
+    pub udp: UdpOptions,
+    pub websocket: WebSocketOptions,
+    pub dns_expected_value: Option<String>,
+    pub icmp: IcmpOptions,
+    pub sql: SqlOptions,
 }
 
 #[derive(Clone)]
 struct ProbeReplicaScript {
     pub script: String,
+    pub timeout: Duration,
+    pub environment: IndexMap<String, String>,
+    pub working_directory: Option<PathBuf>,
 }
 
 #[derive(Clone)]
 enum ProbeReplica {
-    Poll(ProbeReplicaTarget, ProbeReplicaPoll),
+    Poll(ProbeReplicaTarget, Box<ProbeReplicaPoll>),
     Script(ProbeReplicaTarget, ProbeReplicaScript),
 }
 
-fn make_default_headers() -> HeaderMap {
-    let mut headers = HeaderMap::new();
-
-    headers.insert(
-        USER_AGENT,
-        format!("vigil (+{})", APP_CONF.branding.page_url.as_str())
-            .parse()
-            .unwrap(),
-    );
-
-    headers
+impl ProbeReplica {
+    fn target(&self) -> &ProbeReplicaTarget {
+        match self {
+            ProbeReplica::Poll(probe_replica_target, _)
+            | ProbeReplica::Script(probe_replica_target, _) => probe_replica_target,
+        }
+    }
 }
 
-fn map_poll_replicas() -> Vec<ProbeReplica> {
+fn map_poll_replicas(states: &ServiceStates) -> Vec<(ProbeReplica, Duration)> {
     let mut replica_list = Vec::new();
 
-    // Acquire states
-    let states = &PROBER_STORE.read().states;
-
     // Map replica URLs to be probed
     for (probe_id, probe) in states.probes.iter() {
         for (node_id, node) in probe.nodes.iter() {
@@ -134,21 +164,37 @@ fn map_poll_replicas() -> Vec<ProbeReplica> {
                         //   the replica scan is performed. As this whole operation can take time, \
                         //   it could lock all the pipelines depending on the shared store data \
                         //   (eg. the reporter HTTP API).
-                        replica_list.push(ProbeReplica::Poll(
+                        let probe_replica = ProbeReplica::Poll(
                             ProbeReplicaTarget {
                                 probe_id: probe_id.clone(),
                                 node_id: node_id.clone(),
                                 replica_id: replica_id.clone(),
                             },
-                            ProbeReplicaPoll {
+                            Box::new(ProbeReplicaPoll {
                                 replica_url: replica_url.clone(),
+                                schedule: node.schedule.clone(),
                                 http_headers: node.http_headers.clone(),
                                 http_method: node.http_method,
                                 http_body: node.http_body.clone(),
                                 http_cache_buster: node.http_cache_buster,
+                                http_client: node.http_client.clone(),
+                                http_status_healthy_above: node.http_status_healthy_above,
+                                http_status_healthy_below: node.http_status_healthy_below,
+                                http_headers_match: node.http_headers_healthy_match.clone(),
                                 body_match: node.http_body_healthy_match.clone(),
-                            },
-                        ));
+                                body_unmatch: node.http_body_unhealthy_match.clone(),
+                                body_json_assertions: node.http_body_json_assertions.clone(),
+                                tcp_send: node.tcp_send.clone(),
+                                tcp_response_match: node.tcp_response_healthy_match.clone(),
+                                udp: node.udp.clone(),
+                                websocket: node.websocket.clone(),
+                                dns_expected_value: node.dns_expected_value.clone(),
+                                icmp: node.icmp.clone(),
+                                sql: node.sql.clone(),
+                            }),
+                        );
+
+                        replica_list.push((probe_replica, node.schedule.interval(&node.mode)));
                     }
                 }
             }
@@ -158,12 +204,9 @@ fn map_poll_replicas() -> Vec<ProbeReplica> {
     replica_list
 }
 
-fn map_script_replicas() -> Vec<ProbeReplica> {
+fn map_script_replicas(states: &ServiceStates) -> Vec<(ProbeReplica, Duration)> {
     let mut replica_list = Vec::new();
 
-    // Acquire states
-    let states = &PROBER_STORE.read().states;
-
     // Map scripts to be probed
     for (probe_id, probe) in states.probes.iter() {
         for (node_id, node) in probe.nodes.iter() {
@@ -172,7 +215,7 @@ fn map_script_replicas() -> Vec<ProbeReplica> {
                     if let Some(ref replica_script) = replica.script {
                         // Clone values to scan; this ensure the write lock is not held while \
                         //   the script execution is performed. Same as in `map_poll_replicas()`.
-                        replica_list.push(ProbeReplica::Script(
+                        let probe_replica = ProbeReplica::Script(
                             ProbeReplicaTarget {
                                 probe_id: probe_id.clone(),
                                 node_id: node_id.clone(),
@@ -180,8 +223,16 @@ fn map_script_replicas() -> Vec<ProbeReplica> {
                             },
                             ProbeReplicaScript {
                                 script: replica_script.clone(),
+                                timeout: Duration::from_secs(
+                                    node.script_timeout
+                                        .unwrap_or(APP_CONF.load().metrics.script_timeout),
+                                ),
+                                environment: node.script_environment.clone(),
+                                working_directory: node.script_working_directory.clone(),
                             },
-                        ));
+                        );
+
+                        replica_list.push((probe_replica, node.schedule.interval(&node.mode)));
                     }
                 }
             }
@@ -191,277 +242,360 @@ fn map_script_replicas() -> Vec<ProbeReplica> {
     replica_list
 }
 
-fn proceed_replica_probe_poll_with_retry(
-    replica_url: &ReplicaUrl,
-    http_cache_buster: bool,
-    http_headers: &HeaderMap,
-    http_method: Option<HttpMethod>,
-    http_body: &Option<String>,
-    body_match: &Option<Regex>,
-) -> (Status, Option<Duration>) {
-    let (mut status, mut latency, mut retry_count) = (Status::Dead, None, 0);
+async fn proceed_replica_probe_poll_with_retry(
+    probe_replica_poll: &ProbeReplicaPoll,
+) -> (Status, ServiceStatesProbeNodeReplicaMetrics, Option<String>) {
+    let (mut status, mut metrics, mut message, mut retry_count) = (
+        Status::Dead,
+        ServiceStatesProbeNodeReplicaMetrics::default(),
+        None,
+        0,
+    );
+
+    while retry_count <= probe_replica_poll.schedule.retry() && status == Status::Dead {
+        // Hold before retrying (not before the first attempt)
+        if retry_count > 0 {
+            sleep(probe_replica_poll.schedule.retry_delay()).await;
+        }
 
-    while retry_count <= APP_CONF.metrics.poll_retry && status == Status::Dead {
         tracing::debug!(
             "will probe replica: {:?} with retry count: {}",
-            replica_url,
+            probe_replica_poll.replica_url,
             retry_count
         );
 
-        thread::sleep(Duration::from_millis(PROBE_HOLD_MILLISECONDS));
-
-        let probe_results = proceed_replica_probe_poll(
-            replica_url,
-            http_cache_buster,
-            http_headers,
-            http_method,
-            http_body,
-            body_match,
-        );
-
-        status = probe_results.0;
-        latency = Some(probe_results.1);
+        (status, metrics, message) = proceed_replica_probe_poll(probe_replica_poll).await;
 
         // Increment retry count (for next attempt)
         retry_count += 1;
     }
 
-    (status, latency)
+    (status, metrics, message)
 }
 
-fn proceed_replica_probe_poll(
-    replica_url: &ReplicaUrl,
-    http_cache_buster: bool,
-    http_headers: &HeaderMap,
-    http_method: Option<HttpMethod>,
-    http_body: &Option<String>,
-    body_match: &Option<Regex>,
-) -> (Status, Duration) {
+async fn proceed_replica_probe_poll(
+    probe_replica_poll: &ProbeReplicaPoll,
+) -> (Status, ServiceStatesProbeNodeReplicaMetrics, Option<String>) {
+    let replica_url = &probe_replica_poll.replica_url;
+    let delay_dead = probe_replica_poll.schedule.delay_dead();
     let start_time = SystemTime::now();
 
-    let (is_up, poll_duration) = match replica_url {
-        &ReplicaUrl::Icmp(ref host) => proceed_replica_probe_poll_icmp(host),
-        &ReplicaUrl::Tcp(ref host, port) => proceed_replica_probe_poll_tcp(host, port),
-        &ReplicaUrl::Http(ref url) | &ReplicaUrl::Https(ref url) => {
-            proceed_replica_probe_poll_http(
+    let mut metrics = ServiceStatesProbeNodeReplicaMetrics::default();
+
+    // Failure message (only reported by probes that can tell why they failed)
+    let mut message = None;
+
+    let (status, poll_duration) = match replica_url {
+        ReplicaUrl::Icmp(host) => {
+            let (host, icmp) = (host.clone(), probe_replica_poll.icmp.clone());
+
+            // Notice: ICMP pings are sent from blocking sockets, thus from the blocking pool. \
+            //   This holds a blocking thread for the whole ping burst, though at most \
+            //   'poll_parallelism' of them, as replicas only get probed with a scheduler permit.
+            let (status, rtt, icmp) = task::spawn_blocking(move || {
+                proceed_replica_probe_poll_icmp(&host, delay_dead, &icmp)
+            })
+            .await
+            .unwrap_or((Status::Dead, None, None));
+
+            metrics.icmp = icmp;
+
+            (status, rtt)
+        }
+        ReplicaUrl::Tcp(host, port) => {
+            proceed_replica_probe_poll_tcp(
+                host,
+                *port,
+                delay_dead,
+                &probe_replica_poll.tcp_send,
+                &probe_replica_poll.tcp_response_match,
+            )
+            .await
+        }
+        ReplicaUrl::Udp(host, port) => {
+            proceed_replica_probe_poll_udp(host, *port, delay_dead, &probe_replica_poll.udp).await
+        }
+        ReplicaUrl::Http(url) | ReplicaUrl::Https(url) => {
+            proceed_replica_probe_poll_http(url, probe_replica_poll).await
+        }
+        ReplicaUrl::Ws(url) | ReplicaUrl::Wss(url) => {
+            let (status, latency, websocket_message) = proceed_replica_probe_poll_websocket(
                 url,
-                http_cache_buster,
-                http_headers,
-                http_method,
-                http_body,
-                body_match,
+                delay_dead,
+                &probe_replica_poll.http_headers,
+                &probe_replica_poll.http_client.tls,
+                &probe_replica_poll.websocket,
             )
+            .await;
+
+            message = websocket_message;
+
+            (status, latency)
         }
-    };
+        ReplicaUrl::Grpc(host, port, service) | ReplicaUrl::Grpcs(host, port, service) => {
+            proceed_replica_probe_poll_grpc(
+                host,
+                *port,
+                service,
+                matches!(replica_url, ReplicaUrl::Grpcs(..)),
+                delay_dead,
+                &probe_replica_poll.http_headers,
+                &probe_replica_poll.http_client.tls,
+            )
+            .await
+        }
+        ReplicaUrl::Protocol(protocol, host, port) => {
+            let (status, latency, protocol_message) =
+                proceed_replica_probe_poll_protocol(protocol, host, *port, delay_dead).await;
 
-    let duration_latency = match poll_duration {
-        Some(poll_duration) => poll_duration,
-        None => SystemTime::now()
-            .duration_since(start_time)
-            .unwrap_or(Duration::from_secs(0)),
+            message = protocol_message;
+
+            (status, latency)
+        }
+        ReplicaUrl::Database(database, url) => {
+            let (status, latency, sql_message) =
+                proceed_replica_probe_poll_sql(database, url, delay_dead, &probe_replica_poll.sql)
+                    .await;
+
+            message = sql_message;
+
+            (status, latency)
+        }
+        ReplicaUrl::Tls(host, port) => {
+            // Notice: the TLS handshake is performed upon checking the certificate (see below)
+            tracing::debug!("prober poll will fire for tls target: {}:{}", host, port);
+
+            (Status::Healthy, None)
+        }
+        ReplicaUrl::Dns(host, port, name, record_type) => {
+            proceed_replica_probe_poll_dns(
+                host,
+                *port,
+                name,
+                *record_type,
+                delay_dead,
+                &probe_replica_poll.dns_expected_value,
+            )
+            .await
+        }
     };
 
-    if is_up {
-        // Probe reports as sick?
-        if duration_latency >= Duration::from_secs(APP_CONF.metrics.poll_delay_sick) {
-            return (Status::Sick, duration_latency);
+    if status == Status::Dead {
+        // Notice: the latency of a dead replica is not meaningful, thus it is not reported \
+        //   (though other metrics are, eg. the ICMP packet loss)
+        return (Status::Dead, metrics, message);
+    }
+
+    // Check presented certificate? (for TLS-enabled replicas, which are not already dead)
+    let (status, poll_duration) = match replica_url.tls_target() {
+        Some((host, port)) => {
+            match proceed_replica_probe_poll_tls(&host, port, &probe_replica_poll.http_client).await
+            {
+                (Status::Dead, _, _) => return (Status::Dead, metrics, message),
+                (tls_status, handshake, tls) => {
+                    metrics.tls = tls;
+
+                    if tls_status == Status::Sick {
+                        (Status::Sick, poll_duration.or(handshake))
+                    } else {
+                        (status, poll_duration.or(handshake))
+                    }
+                }
+            }
         }
+        None => (status, poll_duration),
+    };
 
-        (Status::Healthy, duration_latency)
-    } else {
-        (Status::Dead, duration_latency)
+    // Acquire latency as measured by the probe (fallback on the whole probe duration if the \
+    //   probe could not measure it)
+    let duration_latency = poll_duration.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(start_time)
+            .unwrap_or(Duration::from_secs(0))
+    });
+
+    metrics.latency = Some(duration_latency.as_millis() as u64);
+
+    // Probe reports as sick? (either from its own checks, or from its latency)
+    if duration_latency >= probe_replica_poll.schedule.delay_sick() {
+        tracing::debug!(
+            "prober poll latency for replica: {:?} is above sick delay: {:?}",
+            replica_url,
+            duration_latency
+        );
+
+        return (Status::Sick, metrics, message);
     }
-}
 
-fn proceed_replica_probe_poll_icmp(host: &str) -> (bool, Option<Duration>) {
-    // Notice: a dummy port of value '0' is set here, so that we can resolve the host to an actual
-    //   IP address using the standard library, which avoids depending on an additional library.
-    let address_results = (host, 0).to_socket_addrs();
+    (status, metrics, message)
+}
 
-    // Storage variable for the maximum round-trip-time found for received ping responses
-    let mut maximum_rtt = None;
+async fn proceed_replica_probe_poll_tcp(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    tcp_send: &Option<String>,
+    response_match: &Option<Regex>,
+) -> (Status, Option<Duration>) {
+    let address_results = lookup_host((host, port)).await;
 
-    let socket_type = APP_CONF
-        .plugins
-        .icmp
-        .as_ref()
-        .map(|x| x.socket_type)
-        .unwrap_or_default();
+    // Storage variable for the maximum latency found for probed addresses
+    let mut maximum_latency = None;
 
     match address_results {
         Ok(address) => {
-            // Notice: the ICMP probe checker is a bit special, in the sense that it checks all \
-            //   resolved addresses. As we check for an host health at the IP level (ie. not at \
-            //   the application layer level), checking only the first host in the list is not \
-            //   sufficient for the whole replica group to be up. This can be used as an handy way \
-            //   to check for the health of a group of IP hosts, configured in a single DNS record.
+            // Notice: as for ICMP probes, all resolved addresses are checked, as a single \
+            //   unreachable address is sufficient for the whole replica to be considered down.
             let address_values: Vec<SocketAddr> = address.collect();
 
-            if !address_values.is_empty() {
+            if address_values.is_empty() {
                 tracing::debug!(
-                    "prober poll will fire for icmp host: {} ({} targets)",
+                    "prober poll did not resolve any address for tcp replica: {}:{}",
                     host,
-                    address_values.len()
+                    port
                 );
 
-                // As ICMP pings require a lower-than-usual timeout, an hard-coded ICMP \
-                //   timeout value is used by default, though the configured dead delay value \
-                //   is preferred in the event it is lower than the hard-coded value (unlikely \
-                //   though possible in some setups).
-                let pinger_timeout = Duration::from_secs(min(
-                    PROBE_ICMP_TIMEOUT_SECONDS,
-                    APP_CONF.metrics.poll_delay_dead,
-                ));
+                // Consider empty as a failure
+                return (Status::Dead, None);
+            }
 
-                // Probe all returned addresses (sequentially)
-                for address_value in &address_values {
-                    let address_ip = address_value.ip();
+            tracing::debug!(
+                "prober poll will fire for tcp host: {}:{} ({} targets)",
+                host,
+                port,
+                address_values.len()
+            );
 
-                    tracing::debug!(
-                        "prober poll will send icmp ping to target: {} from host: {}",
-                        address_ip,
-                        host
-                    );
+            // Probe all returned addresses (sequentially)
+            for address_value in &address_values {
+                match proceed_replica_probe_poll_tcp_address(
+                    address_value,
+                    timeout,
+                    tcp_send,
+                    response_match,
+                )
+                .await
+                {
+                    Some(latency) => {
+                        tracing::debug!("prober poll success for tcp target: {}", address_value);
 
-                    // Acquire ping start time (used for RTT calculation)
-                    let ping_start_time = SystemTime::now();
-
-                    // Ping target IP address
-                    match ping(
-                        address_ip,
-                        Some(pinger_timeout),
-                        None,
-                        None,
-                        None,
-                        None,
-                        socket2::Type::from(socket_type),
-                    ) {
-                        Ok(_) => {
-                            tracing::debug!(
-                                "got prober poll response for icmp target: {} from host: {}",
-                                address_ip,
-                                host
-                            );
-
-                            // Process ping RTT
-                            let ping_rtt = SystemTime::now()
-                                .duration_since(ping_start_time)
-                                .unwrap_or(Duration::from_secs(0));
-
-                            // Do not return (consider address as reachable)
-                            // Notice: update maximum observed round-trip-time, if higher than \
-                            //   last highest observed.
-                            maximum_rtt = match maximum_rtt {
-                                Some(maximum_rtt) => {
-                                    if ping_rtt > maximum_rtt {
-                                        Some(ping_rtt)
-                                    } else {
-                                        Some(maximum_rtt)
-                                    }
-                                }
-                                None => Some(ping_rtt),
-                            };
-                        }
-                        Err(err) => {
-                            tracing::debug!(
-                                "prober poll error for icmp target: {} from host: {} (error: {})",
-                                address_ip,
-                                host,
-                                err
-                            );
-
-                            // Consider ICMP errors as a failure
-                            return (false, None);
-                        }
+                        // Notice: update maximum observed latency, if higher than last highest \
+                        //   observed.
+                        maximum_latency = maximum_latency.max(Some(latency));
                     }
+                    None => return (Status::Dead, None),
                 }
-            } else {
-                tracing::debug!(
-                    "prober poll did not resolve any address for icmp replica: {}",
-                    host
-                );
-
-                // Consider empty as a failure
-                return (false, None);
             }
         }
         Err(err) => {
             tracing::error!(
-                "prober poll address for icmp replica is invalid: {} (error: {})",
+                "prober poll address for tcp replica is invalid: {}:{} (error: {})",
                 host,
+                port,
                 err
             );
 
             // Consider invalid URL as a failure
-            return (false, None);
+            return (Status::Dead, None);
         }
     };
 
-    // If there was no early return, consider all the hosts as reachable for replica
-    (true, maximum_rtt)
+    // If there was no early return, consider all the addresses as reachable for replica
+    (Status::Healthy, maximum_latency)
+}
+
+async fn proceed_replica_probe_poll_tcp_address(
+    address: &SocketAddr,
+    timeout: Duration,
+    tcp_send: &Option<String>,
+    response_match: &Option<Regex>,
+) -> Option<Duration> {
+    // Acquire connect start time (used for latency calculation, which excludes the time spent \
+    //   resolving the host, but includes the time spent exchanging data)
+    let connect_start_time = SystemTime::now();
+
+    // Notice: the timeout applies to the whole exchange (connect, send and expect), otherwise a \
+    //   slow peer could hold the probe for several times the configured timeout.
+    let exchanged = with_timeout(
+        timeout,
+        exchange_tcp_address(address, tcp_send, response_match),
+    )
+    .await
+    .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()));
+
+    match exchanged {
+        Ok(true) => SystemTime::now().duration_since(connect_start_time).ok(),
+        Ok(false) => None,
+        Err(err) => {
+            tracing::debug!(
+                "prober poll error for tcp target: {} (error: {})",
+                address,
+                err
+            );
+
+            None
+        }
+    }
 }
 
-fn proceed_replica_probe_poll_tcp(host: &str, port: u16) -> (bool, Option<Duration>) {
-    let address_results = (host, port).to_socket_addrs();
+async fn exchange_tcp_address(
+    address: &SocketAddr,
+    tcp_send: &Option<String>,
+    response_match: &Option<Regex>,
+) -> io::Result<bool> {
+    let mut stream = TcpStream::connect(address).await?;
 
-    match address_results {
-        Ok(mut address) => {
-            if let Some(address_value) = address.next() {
-                tracing::debug!("prober poll will fire for tcp target: {}", address_value);
-
-                return match TcpStream::connect_timeout(
-                    &address_value,
-                    Duration::from_secs(APP_CONF.metrics.poll_delay_dead),
-                ) {
-                    Ok(_) => {
-                        tracing::debug!("prober poll success for tcp target: {}", address_value);
+    // Send payload? (if configured)
+    if let Some(tcp_send) = tcp_send {
+        stream.write_all(tcp_send.as_bytes()).await?;
+    }
 
-                        (true, None)
-                    }
-                    Err(err) => {
-                        tracing::debug!(
-                            "prober poll error for tcp target: {} (error: {})",
-                            address_value,
-                            err
-                        );
+    // Check response for match? (if configured)
+    if let Some(response_match) = response_match {
+        let mut response = Vec::new();
+        let mut buffer = [0; 1024];
 
-                        (false, None)
-                    }
-                };
-            } else {
-                tracing::debug!(
-                    "prober poll did not resolve any address for tcp replica: {}:{}",
-                    host,
-                    port
-                );
+        // Read until the response matches, or until the peer is done sending
+        loop {
+            let read = stream.read(&mut buffer).await?;
+
+            if read == 0 {
+                break;
+            }
+
+            response.extend_from_slice(&buffer[..read]);
+
+            if response.len() >= PROBE_TCP_RESPONSE_MAXIMUM_BYTES
+                || response_match.is_match(&String::from_utf8_lossy(&response))
+            {
+                break;
             }
         }
-        Err(err) => {
-            tracing::error!(
-                "prober poll address for tcp replica is invalid: {}:{} (error: {})",
-                host,
-                port,
-                err
-            );
-        }
-    };
 
-    (false, None)
+        let response = String::from_utf8_lossy(&response);
+
+        tracing::debug!(
+            "checking prober poll response for tcp target: {} for any match: {}",
+            address,
+            response
+        );
+
+        // Doesnt match? Consider as DOWN.
+        return Ok(response_match.is_match(&response));
+    }
+
+    Ok(true)
 }
 
-fn proceed_replica_probe_poll_http(
+async fn proceed_replica_probe_poll_http(
     url: &str,
-    http_cache_buster: bool,
-    http_headers: &HeaderMap,
-    http_method: Option<HttpMethod>,
-    http_body: &Option<String>,
-    body_match: &Option<Regex>,
-) -> (bool, Option<Duration>) {
+    probe_replica_poll: &ProbeReplicaPoll,
+) -> (Status, Option<Duration>) {
     // Acquire query string separator (if the URL already contains a query string, use append mode)
     let query_separator = if url.contains('?') { "&" } else { "?" };
 
     // Generate URL with cache buster, to bypass any upstream cache (eg. CDN cache layer)
-    let url_bang = if http_cache_buster {
+    let url_bang = if probe_replica_poll.http_cache_buster {
         format!(
             "{}{}_overvakt={}",
             url,
@@ -472,15 +606,24 @@ fn proceed_replica_probe_poll_http(
         url.to_string()
     };
 
+    // Check response body? (any body check requires the body to be fetched)
+    let has_body_checks = probe_replica_poll.body_match.is_some()
+        || probe_replica_poll.body_unmatch.is_some()
+        || !probe_replica_poll.body_json_assertions.is_empty();
+
     // Acquire effective HTTP method to use for probe query
-    let effective_http_method = http_method.as_ref().unwrap_or(if body_match.is_some() {
-        &HttpMethod::Get
-    } else {
-        &HttpMethod::Head
-    });
+    let effective_http_method =
+        probe_replica_poll
+            .http_method
+            .as_ref()
+            .unwrap_or(if has_body_checks {
+                &HttpMethod::Get
+            } else {
+                &HttpMethod::Head
+            });
 
     // Acquire effective HTTP body to use for probe query (for POST methods only)
-    let effective_http_body = http_body.as_ref().map(String::as_str).unwrap_or_default();
+    let effective_http_body = probe_replica_poll.http_body.as_deref().unwrap_or_default();
 
     // Probe target, with provided HTTP method and body (if any)
     tracing::debug!(
@@ -490,69 +633,119 @@ fn proceed_replica_probe_poll_http(
         &effective_http_body
     );
 
+    // Acquire request start time (used for latency calculation)
+    let request_start_time = SystemTime::now();
+
+    let client = match acquire_client(&probe_replica_poll.http_client) {
+        Ok(client) => client,
+        Err(err) => {
+            tracing::error!(
+                "prober poll could not build http client for target: {} (error: {})",
+                &url_bang,
+                err
+            );
+
+            return (Status::Dead, None);
+        }
+    };
+
     let response = match effective_http_method {
-        HttpMethod::Head => PROBE_HTTP_CLIENT.head(&url_bang),
-        HttpMethod::Get => PROBE_HTTP_CLIENT.get(&url_bang),
-        HttpMethod::Post => PROBE_HTTP_CLIENT
+        HttpMethod::Head => client.head(&url_bang),
+        HttpMethod::Get => client.get(&url_bang),
+        HttpMethod::Post => client
             .post(&url_bang)
-            .body(reqwest::blocking::Body::from(
-                effective_http_body.to_string(),
-            )),
-        HttpMethod::Put => PROBE_HTTP_CLIENT
+            .body(reqwest::Body::from(effective_http_body.to_string())),
+        HttpMethod::Put => client
             .put(&url_bang)
-            .body(reqwest::blocking::Body::from(
-                effective_http_body.to_string(),
-            )),
-        HttpMethod::Patch => {
-            PROBE_HTTP_CLIENT
-                .patch(&url_bang)
-                .body(reqwest::blocking::Body::from(
-                    effective_http_body.to_string(),
-                ))
-        }
+            .body(reqwest::Body::from(effective_http_body.to_string())),
+        HttpMethod::Patch => client
+            .patch(&url_bang)
+            .body(reqwest::Body::from(effective_http_body.to_string())),
     }
-    .headers(http_headers.clone())
-    .send();
+    .headers(probe_replica_poll.http_headers.clone())
+    .send()
+    .await;
 
     match response {
         Ok(response_inner) => {
             let status_code = response_inner.status().as_u16();
 
+            // Notice: latency is measured up to the response headers, as the response body is \
+            //   only read if it needs to be checked.
+            let request_latency = SystemTime::now().duration_since(request_start_time).ok();
+
             tracing::debug!(
                 "prober poll result received for http target: {} with status: {}",
                 &url_bang,
                 status_code
             );
 
-            // Consider as UP?
-            if status_code >= APP_CONF.metrics.poll_http_status_healthy_above
-                && status_code < APP_CONF.metrics.poll_http_status_healthy_below
-            {
-                // Check response body for match? (if configured)
-                if let &Some(ref body_match_regex) = body_match {
-                    if let Ok(text) = response_inner.text() {
-                        tracing::debug!(
-                        "checking prober poll response text for http target: {} for any match: {}",
-                        &url_bang, &text
+            // Acquire accepted status range (node-level ranges override the global ones)
+            let (status_healthy_above, status_healthy_below) = {
+                let metrics = &APP_CONF.load().metrics;
+
+                (
+                    probe_replica_poll
+                        .http_status_healthy_above
+                        .unwrap_or(metrics.poll_http_status_healthy_above),
+                    probe_replica_poll
+                        .http_status_healthy_below
+                        .unwrap_or(metrics.poll_http_status_healthy_below),
+                )
+            };
+
+            // Consider as DOWN? (status is out of the accepted range)
+            if status_code < status_healthy_above || status_code >= status_healthy_below {
+                return (Status::Dead, None);
+            }
+
+            // Check response headers for match? (if configured)
+            for (header_name, header_match) in probe_replica_poll.http_headers_match.iter() {
+                let header_value = response_inner
+                    .headers()
+                    .get(header_name.as_str())
+                    .and_then(|value| value.to_str().ok());
+
+                // Missing or doesnt match? Consider as DOWN.
+                if !header_value.is_some_and(|value| header_match.is_match(value)) {
+                    tracing::debug!(
+                        "prober poll response header: {} for http target: {} does not match: {}",
+                        header_name,
+                        &url_bang,
+                        header_match
                     );
 
-                        // Doesnt match? Consider as DOWN.
-                        if !body_match_regex.is_match(&text) {
-                            return (false, None);
-                        }
-                    } else {
+                    return (Status::Dead, None);
+                }
+            }
+
+            // Check response body? (if configured)
+            if has_body_checks {
+                let text = match response_inner.text().await {
+                    Ok(text) => text,
+                    Err(_) => {
                         tracing::debug!(
                             "could not unpack response text for http target: {}",
                             &url_bang
                         );
 
                         // Consider as DOWN (the response text could not be checked)
-                        return (false, None);
+                        return (Status::Dead, None);
                     }
-                }
+                };
+
+                tracing::debug!(
+                    "checking prober poll response text for http target: {} for any match: {}",
+                    &url_bang,
+                    &text
+                );
 
-                return (true, None);
+                if !proceed_replica_probe_poll_http_body(&text, probe_replica_poll) {
+                    return (Status::Dead, None);
+                }
             }
+
+            return (Status::Healthy, request_latency);
         }
         Err(err) => {
             tracing::debug!(
@@ -564,63 +757,153 @@ fn proceed_replica_probe_poll_http(
     }
 
     // Consider as DOWN.
-    (false, None)
+    (Status::Dead, None)
 }
 
-fn proceed_replica_probe_script(script: &str) -> (Status, Option<Duration>) {
-    let start_time = SystemTime::now();
+fn proceed_replica_probe_poll_http_body(text: &str, probe_replica_poll: &ProbeReplicaPoll) -> bool {
+    // Doesnt match? Consider as DOWN.
+    if let Some(ref body_match) = probe_replica_poll.body_match {
+        if !body_match.is_match(text) {
+            return false;
+        }
+    }
+
+    // Does match the negative pattern? Consider as DOWN.
+    if let Some(ref body_unmatch) = probe_replica_poll.body_unmatch {
+        if body_unmatch.is_match(text) {
+            tracing::debug!("prober poll response text matches: {}", body_unmatch);
+
+            return false;
+        }
+    }
+
+    // Check JSON assertions? (the body must be valid JSON then)
+    if !probe_replica_poll.body_json_assertions.is_empty() {
+        let body = match serde_json::from_str(text) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::debug!("prober poll response text is not json (error: {})", err);
+
+                return false;
+            }
+        };
+
+        for assertion in probe_replica_poll.body_json_assertions.iter() {
+            if !assertion.is_match(&body) {
+                tracing::debug!(
+                    "prober poll response json does not satisfy assertion: {} {:?} {}",
+                    assertion.path,
+                    assertion.operator,
+                    assertion.value
+                );
+
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+async fn proceed_replica_probe_poll_tls(
+    host: &str,
+    port: u16,
+    options: &HttpClientOptions,
+) -> (
+    Status,
+    Option<Duration>,
+    Option<ServiceStatesProbeNodeReplicaMetricsTls>,
+) {
+    match fetch_certificate(
+        host,
+        port,
+        Duration::from_secs(
+            options
+                .timeout
+                .unwrap_or(APP_CONF.load().metrics.poll_delay_dead),
+        ),
+        &options.tls,
+    )
+    .await
+    {
+        Ok(certificate) => {
+            let expiry_days = certificate.expiry_days();
 
-    let status = match run_script::run(script, &Vec::new(), &ScriptOptions::new()) {
-        Ok((code, _, _)) => {
             tracing::debug!(
-                "prober script execution succeeded with return code: {}",
-                code
+                "prober poll certificate for tls target: {}:{} expires in {} days",
+                host,
+                port,
+                expiry_days
             );
 
-            // Return code '0' goes for 'healthy', '1' goes for 'sick'; any other code is 'dead'
-            match code {
-                0 => Status::Healthy,
-                1 => Status::Sick,
-                _ => Status::Dead,
+            let tls = ServiceStatesProbeNodeReplicaMetricsTls { expiry_days };
+
+            // Certificate expires soon? Consider as SICK.
+            if expiry_days < APP_CONF.load().metrics.poll_tls_expiry_sick_below {
+                return (Status::Sick, Some(certificate.handshake), Some(tls));
             }
+
+            (Status::Healthy, Some(certificate.handshake), Some(tls))
         }
         Err(err) => {
-            tracing::error!("prober script execution failed with error: {}", err);
+            // Notice: this includes expired, self-signed and hostname-mismatched certificates
+            tracing::debug!(
+                "prober poll certificate check failed for tls target: {}:{} (error: {})",
+                host,
+                port,
+                err
+            );
 
-            Status::Dead
+            (Status::Dead, None, None)
         }
-    };
-
-    (status, SystemTime::now().duration_since(start_time).ok())
+    }
 }
 
-fn dispatch_replica(probe_replica: &ProbeReplica) {
+async fn dispatch_replica(probe_replica: &ProbeReplica) {
     let probe_id: &String;
     let node_id: &String;
     let replica_id: &String;
 
-    // Acquire replica status (with optional latency)
-    let (replica_status, replica_latency) = match probe_replica {
+    // Acquire replica status (with its metrics, and optional failure message)
+    let (replica_status, replica_metrics, replica_message) = match probe_replica {
         ProbeReplica::Poll(probe_replica_target, probe_replica_poll) => {
             probe_id = &probe_replica_target.probe_id;
             node_id = &probe_replica_target.node_id;
             replica_id = &probe_replica_target.replica_id;
 
-            proceed_replica_probe_poll_with_retry(
-                &probe_replica_poll.replica_url,
-                probe_replica_poll.http_cache_buster,
-                &probe_replica_poll.http_headers,
-                probe_replica_poll.http_method,
-                &probe_replica_poll.http_body,
-                &probe_replica_poll.body_match,
-            )
+            proceed_replica_probe_poll_with_retry(probe_replica_poll).await
         }
         ProbeReplica::Script(probe_replica_target, probe_replica_script) => {
             probe_id = &probe_replica_target.probe_id;
             node_id = &probe_replica_target.node_id;
             replica_id = &probe_replica_target.replica_id;
 
-            proceed_replica_probe_script(&probe_replica_script.script)
+            let probe_replica_script = probe_replica_script.clone();
+
+            // Notice: scripts are waited for synchronously, thus from the blocking pool
+            let (status, latency, message) = task::spawn_blocking(move || {
+                proceed_replica_probe_script(
+                    &probe_replica_script.script,
+                    probe_replica_script.timeout,
+                    &probe_replica_script.environment,
+                    &probe_replica_script.working_directory,
+                )
+            })
+            .await
+            .unwrap_or_else(|err| {
+                (
+                    Status::Dead,
+                    None,
+                    Some(format!("script could not be waited for: {}", err)),
+                )
+            });
+
+            let metrics = ServiceStatesProbeNodeReplicaMetrics {
+                latency: latency.map(|duration| duration.as_millis() as u64),
+                ..Default::default()
+            };
+
+            (status, metrics, message)
         }
     };
 
@@ -641,86 +924,168 @@ fn dispatch_replica(probe_replica: &ProbeReplica) {
                 if let Some(ref mut replica) = node.replicas.get_mut(replica_id) {
                     replica.status = replica_status;
 
-                    replica.metrics.latency =
-                        replica_latency.map(|duration| duration.as_millis() as u64);
+                    replica.metrics = replica_metrics;
+                    replica.message = replica_message;
+                    replica.checked = Some(SystemTime::now());
                 }
             }
         }
     }
 }
 
-fn dispatch_replicas_in_threads(replicas: &[ProbeReplica], parallelism: u16) {
-    // Acquire chunk size (round to the highest unit if there is a remainder)
-    let mut chunk_size = replicas.len() / parallelism as usize;
+async fn dispatch_scheduled(mode: Mode) {
+    let mut schedule = Schedule::new();
+    let mut limiter: Option<(u16, Arc<Semaphore>)> = None;
 
-    if replicas.len() % parallelism as usize > 0 {
-        chunk_size += 1;
-    }
+    // Notice: replicas only get re-mapped when the prober store was (re)initialized, as mapping \
+    //   them clones all of their options.
+    let mut replicas: IndexMap<ProbeReplicaTarget, Arc<ProbeReplica>> = IndexMap::new();
+    let mut generation = None;
 
-    // Anything to scan?
-    if chunk_size > 0 {
-        let start_time = SystemTime::now();
+    // Notice: each probed replica reports its completion, so that it can get probed again once \
+    //   due (replicas are never probed twice at the same time).
+    let (sender, mut receiver) = mpsc::unbounded_channel();
 
-        // Initialize probing threads registry (this helps split the work into multiple parallel \
-        //   synchronous threads, where parallelism can be increased on large Övervakt setups)
-        let mut prober_threads = Vec::new();
+    loop {
+        let now = Instant::now();
 
-        for replicas_chunk in replicas.chunks(chunk_size) {
-            // Re-map list of chunked replicas so that they can be passed to their thread
-            let replicas_chunk: Vec<ProbeReplica> = replicas_chunk.to_vec();
+        // Synchronize the schedule with the current replicas (if they were reloaded)
+        let mapped = {
+            let store = PROBER_STORE.read();
 
-            // Append probing chunk into its own synchronous thread
-            prober_threads.push(thread::spawn(move || {
-                for probe_replica in replicas_chunk {
-                    dispatch_replica(&probe_replica);
-                }
-            }));
-        }
+            if generation != Some(store.generation) {
+                generation = Some(store.generation);
 
-        let prober_threads_len = prober_threads.len();
+                Some(match mode {
+                    Mode::Script => map_script_replicas(&store.states),
+                    _ => map_poll_replicas(&store.states),
+                })
+            } else {
+                None
+            }
+        };
 
-        tracing::debug!(
-            "replicas will get probed in {}/{} threads, on {} total replicas and chunk size of {}",
-            prober_threads_len,
-            parallelism,
-            replicas.len(),
-            chunk_size
-        );
+        if let Some(mapped) = mapped {
+            schedule.synchronize(
+                mapped
+                    .iter()
+                    .map(|(probe_replica, interval)| (probe_replica.target().clone(), *interval))
+                    .collect(),
+                now,
+            );
 
-        // Wait for all parallel probing threads to complete
-        for prober_thread in prober_threads {
-            prober_thread.join().unwrap();
+            replicas = mapped
+                .into_iter()
+                .map(|(probe_replica, _)| (probe_replica.target().clone(), Arc::new(probe_replica)))
+                .collect();
         }
 
-        // Measure total probing duration
-        let probing_duration = SystemTime::now()
-            .duration_since(start_time)
-            .unwrap_or(Duration::from_secs(0));
+        // Bound the number of replicas probed at the same time (probes that are already running \
+        //   hold on to their permit, if parallelism was changed upon reloading the configuration)
+        let parallelism = {
+            let metrics = &APP_CONF.load().metrics;
 
-        tracing::info!(
-            "replicas have been probed with {}/{} threads in {:?}",
-            prober_threads_len,
-            parallelism,
-            probing_duration
-        );
-    }
-}
+            match mode {
+                Mode::Script => metrics.script_parallelism,
+                _ => metrics.poll_parallelism,
+            }
+        };
+
+        let semaphore = match limiter {
+            Some((limiter_parallelism, ref semaphore)) if limiter_parallelism == parallelism => {
+                semaphore.clone()
+            }
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(parallelism as usize));
+
+                limiter = Some((parallelism, semaphore.clone()));
+
+                semaphore
+            }
+        };
+
+        // Probe due replicas, each in its own task (waiting for a permit, if parallelism is \
+        //   exhausted)
+        for probe_replica_target in schedule.take_due(now) {
+            let probe_replica = match replicas.get(&probe_replica_target) {
+                Some(probe_replica) => probe_replica.clone(),
+                None => {
+                    schedule.complete(&probe_replica_target);
 
-fn dispatch_polls() {
-    // Probe hosts
-    dispatch_replicas_in_threads(&map_poll_replicas(), APP_CONF.metrics.poll_parallelism);
+                    continue;
+                }
+            };
+
+            tracing::debug!(
+                "replica probe is due: {}:{}:{}",
+                probe_replica_target.probe_id,
+                probe_replica_target.node_id,
+                probe_replica_target.replica_id
+            );
+
+            let (semaphore, sender) = (semaphore.clone(), sender.clone());
+
+            tokio::spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+
+                // Notice: the probe runs in its own task, so that its completion is reported even \
+                //   if it panicked, otherwise the replica would never get probed again.
+                let probe = tokio::spawn(async move { dispatch_replica(&probe_replica).await });
+
+                if probe.await.is_err() {
+                    tracing::error!(
+                        "replica probe crashed: {}:{}:{}",
+                        probe_replica_target.probe_id,
+                        probe_replica_target.node_id,
+                        probe_replica_target.replica_id
+                    );
+                }
+
+                let _ = sender.send(probe_replica_target);
+            });
+        }
+
+        // Hold until the next replica is due, or until a running probe completes (though, \
+        //   check the store generation regularly so that reloaded replicas get picked up)
+        let wait = schedule
+            .next_due()
+            .map(|next_due| next_due.saturating_duration_since(now))
+            .unwrap_or(Duration::MAX)
+            .min(Duration::from_millis(SCHEDULE_WAIT_MAXIMUM_MILLISECONDS));
+
+        tokio::select! {
+            Some(probe_replica_target) = receiver.recv() => {
+                schedule.complete(&probe_replica_target);
+
+                // Collect any other completed probe
+                while let Ok(probe_replica_target) = receiver.try_recv() {
+                    schedule.complete(&probe_replica_target);
+                }
+            }
+            _ = sleep(wait) => {}
+        }
+    }
 }
 
-fn dispatch_scripts() {
-    // Run scripts
-    dispatch_replicas_in_threads(&map_script_replicas(), APP_CONF.metrics.script_parallelism);
+fn make_schedule_options(service: &Service, node: &ServiceNode) -> ScheduleOptions {
+    // Node-level options override service-level options
+    ScheduleOptions {
+        interval: match node.mode {
+            Mode::Script => node.script_interval.or(service.script_interval),
+            _ => node.poll_interval.or(service.poll_interval),
+        },
+        retry: node.poll_retry.or(service.poll_retry),
+        retry_delay: node.poll_retry_delay.or(service.poll_retry_delay),
+        delay_dead: node.poll_delay_dead.or(service.poll_delay_dead),
+        delay_sick: node.poll_delay_sick.or(service.poll_delay_sick),
+    }
 }
 
-pub fn initialize_store() {
-    // Copy monitored hosts in store (refactor the data structure)
-    let mut store = STORE.write();
+pub fn make_probes(conf: &Config) -> Result<IndexMap<String, ServiceStatesProbe>, InitializeError> {
+    let mut probes = IndexMap::new();
 
-    for (service_id, service) in APP_CONF.probe.service.iter() {
+    // Copy monitored hosts (refactor the data structure)
+    for (service_id, service) in conf.probe.service.iter() {
         let mut probe = ServiceStatesProbe {
             id: service_id.clone(),
             label: service.label.clone(),
@@ -738,11 +1103,63 @@ pub fn initialize_store() {
                 label: node.label.clone(),
                 mode: node.mode.clone(),
                 replicas: IndexMap::new(),
+                schedule: make_schedule_options(service, node),
                 http_headers: node.http_headers.clone(),
                 http_cache_buster: !node.http_no_cache_buster,
+                http_client: HttpClientOptions {
+                    timeout: node
+                        .http_timeout
+                        .or(node.poll_delay_dead.or(service.poll_delay_dead)),
+                    follow_redirects: node.http_follow_redirects,
+                    max_redirects: node.http_max_redirects,
+                    proxy: node.http_proxy.clone(),
+                    tls: TlsOptions {
+                        insecure: node.tls_insecure,
+                        ca_path: node.tls_ca_path.clone(),
+                        client_certificate_path: node.tls_client_certificate_path.clone(),
+                        client_key_path: node.tls_client_key_path.clone(),
+                    },
+                },
                 http_method: node.http_method,
                 http_body: node.http_body.clone(),
+                http_status_healthy_above: node.http_status_healthy_above,
+                http_status_healthy_below: node.http_status_healthy_below,
+                http_headers_healthy_match: node.http_headers_healthy_match.clone(),
                 http_body_healthy_match: node.http_body_healthy_match.clone(),
+                http_body_unhealthy_match: node.http_body_unhealthy_match.clone(),
+                http_body_json_assertions: node.http_body_json_assertions.clone(),
+                tcp_send: node.tcp_send.clone(),
+                tcp_response_healthy_match: node.tcp_response_healthy_match.clone(),
+                udp: UdpOptions {
+                    payload: node
+                        .udp_send_hex
+                        .clone()
+                        .or_else(|| node.udp_send.as_ref().map(|send| send.clone().into_bytes()))
+                        .unwrap_or_default(),
+                    response_match: node.udp_response_healthy_match.clone(),
+                },
+                websocket: WebSocketOptions {
+                    send: node.ws_send.clone(),
+                    response_match: node.ws_response_healthy_match.clone(),
+                },
+                dns_expected_value: node.dns_expected_value.clone(),
+                icmp: IcmpOptions {
+                    count: node.icmp_count,
+                    interval: node.icmp_interval,
+                    loss_sick_above: node.icmp_loss_sick_above,
+                    loss_dead_above: node.icmp_loss_dead_above,
+                    rtt_sick_above: node.icmp_rtt_sick_above,
+                    rtt_dead_above: node.icmp_rtt_dead_above,
+                },
+                sql: SqlOptions {
+                    query: node.sql_query.clone(),
+                    expected_value: node.sql_expected_value.clone(),
+                    value_sick_above: node.sql_value_sick_above,
+                    value_dead_above: node.sql_value_dead_above,
+                },
+                script_timeout: node.script_timeout,
+                script_environment: node.script_environment.clone(),
+                script_working_directory: node.script_working_directory.clone(),
                 rabbitmq: node.rabbitmq_queue.as_ref().map(|queue| {
                     ServiceStatesProbeNodeRabbitMQ {
                         queue: queue.clone(),
@@ -755,21 +1172,32 @@ pub fn initialize_store() {
             // Node with replicas? (might be a poll node)
             if let Some(ref replicas) = node.replicas {
                 if node.mode != Mode::Poll {
-                    panic!("non-poll node cannot have replicas");
+                    return Err(InitializeError::ReplicasNotPoll(
+                        service_id.clone(),
+                        node_id.clone(),
+                    ));
                 }
 
                 for replica in replicas {
+                    let replica_id = ReplicaUrl::make_id(replica);
+
                     tracing::debug!(
                         "prober store: got replica {}:{}:{}",
                         service_id,
                         node_id,
-                        replica
+                        replica_id
                     );
 
-                    let replica_url = ReplicaUrl::parse_from(replica).expect("invalid replica url");
+                    let replica_url = ReplicaUrl::parse_from(replica).map_err(|_| {
+                        InitializeError::InvalidReplicaUrl(
+                            service_id.clone(),
+                            node_id.clone(),
+                            replica_id.clone(),
+                        )
+                    })?;
 
                     probe_node.replicas.insert(
-                        replica.to_string(),
+                        replica_id,
                         ServiceStatesProbeNodeReplica {
                             status: Status::Healthy,
                             url: Some(replica_url),
@@ -777,6 +1205,8 @@ pub fn initialize_store() {
                             metrics: ServiceStatesProbeNodeReplicaMetrics::default(),
                             load: None,
                             report: None,
+                            message: None,
+                            checked: None,
                         },
                     );
                 }
@@ -785,7 +1215,10 @@ pub fn initialize_store() {
             // Node with scripts? (might be a script node)
             if let Some(ref scripts) = node.scripts {
                 if node.mode != Mode::Script {
-                    panic!("non-script node cannot have scripts");
+                    return Err(InitializeError::ScriptsNotScript(
+                        service_id.clone(),
+                        node_id.clone(),
+                    ));
                 }
 
                 for (index, script) in scripts.iter().enumerate() {
@@ -805,6 +1238,8 @@ pub fn initialize_store() {
                             metrics: ServiceStatesProbeNodeReplicaMetrics::default(),
                             load: None,
                             report: None,
+                            message: None,
+                            checked: None,
                         },
                     );
                 }
@@ -813,34 +1248,228 @@ pub fn initialize_store() {
             probe.nodes.insert(node_id.clone(), probe_node);
         }
 
-        store.states.probes.insert(service_id.clone(), probe);
+        probes.insert(service_id.clone(), probe);
     }
 
+    Ok(probes)
+}
+
+pub fn initialize_store() {
+    let probes = make_probes(&APP_CONF.load()).unwrap_or_else(|err| panic!("{}", err));
+
+    let mut store = STORE.write();
+
+    store.states.probes = probes;
+    store.generation += 1;
+
     tracing::info!("initialized prober store");
 }
 
-pub fn run_poll() {
-    loop {
-        tracing::debug!("running a poll probe operation...");
+pub fn reload_store(mut probes: IndexMap<String, ServiceStatesProbe>) {
+    // Clear cached clients, as their options (eg. certificates) might have changed
+    // Notice: the reloaded configuration must be live already, as clients that get built from \
+    //   now on are cached until the next reload.
+    clear_clients();
+    clear_client_configs();
 
-        dispatch_polls();
+    let mut store = STORE.write();
+    let mut previous_probes = std::mem::take(&mut store.states.probes);
+
+    // Carry over the state of unchanged services, nodes and replicas
+    for (probe_id, probe) in probes.iter_mut() {
+        let mut previous_probe = match previous_probes.shift_remove(probe_id) {
+            Some(previous_probe) => previous_probe,
+            None => continue,
+        };
+
+        probe.status = previous_probe.status;
+
+        for (node_id, node) in probe.nodes.iter_mut() {
+            let previous_node = match previous_probe.nodes.shift_remove(node_id) {
+                Some(previous_node) if previous_node.mode == node.mode => previous_node,
+                _ => continue,
+            };
 
-        tracing::info!("ran poll probe operation");
+            node.status = previous_node.status;
 
-        // Hold for next aggregate run
-        thread::sleep(Duration::from_secs(APP_CONF.metrics.poll_interval));
+            match node.mode {
+                Mode::Push | Mode::Local => {
+                    // Reported replicas are not configured, thus they are all carried over
+                    node.replicas = previous_node.replicas;
+                }
+                Mode::Poll | Mode::Script => {
+                    for (replica_id, previous_replica) in previous_node.replicas {
+                        if let Some(replica) = node.replicas.get_mut(&replica_id) {
+                            replica.status = previous_replica.status;
+                            replica.metrics = previous_replica.metrics;
+                            replica.message = previous_replica.message;
+                            replica.checked = previous_replica.checked;
+                        }
+                    }
+                }
+            }
+        }
     }
+
+    store.states.probes = probes;
+    store.generation += 1;
+
+    tracing::info!("reloaded prober store");
 }
 
-pub fn run_script() {
-    loop {
-        tracing::debug!("running a script probe operation...");
+pub async fn run_poll() {
+    tracing::info!("running poll probe scheduler");
+
+    dispatch_scheduled(Mode::Poll).await;
+}
+
+pub async fn run_script() {
+    tracing::info!("running script probe scheduler");
+
+    dispatch_scheduled(Mode::Script).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        dispatch_scripts();
+    use tokio::net::TcpListener;
 
-        tracing::info!("ran script probe operation");
+    const TIMEOUT: Duration = Duration::from_secs(2);
 
-        // Hold for next aggregate run
-        thread::sleep(Duration::from_secs(APP_CONF.metrics.script_interval));
+    // Binds a local server, which answers the first connection with the result of 'respond'
+    async fn serve_once<F>(respond: F) -> SocketAddr
+    where
+        F: FnOnce(Vec<u8>) -> Vec<u8> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+
+            let read = stream.read(&mut buffer).await.unwrap_or(0);
+
+            let _ = stream.write_all(&respond(buffer[..read].to_vec())).await;
+
+            // Hold the connection open, so that probes only stop reading upon a match
+            sleep(Duration::from_secs(10)).await;
+        });
+
+        address
+    }
+
+    fn response_match(pattern: &str) -> Option<Regex> {
+        Some(Regex::new(pattern).unwrap())
+    }
+
+    #[tokio::test]
+    async fn tcp_send_and_expect() {
+        let address = serve_once(|request| {
+            if request == b"PING\r\n" {
+                b"+PONG\r\n".to_vec()
+            } else {
+                b"-ERR\r\n".to_vec()
+            }
+        })
+        .await;
+
+        assert!(proceed_replica_probe_poll_tcp_address(
+            &address,
+            TIMEOUT,
+            &Some("PING\r\n".to_string()),
+            &response_match("^\\+PONG"),
+        )
+        .await
+        .is_some());
+    }
+
+    #[tokio::test]
+    async fn tcp_expect_mismatch() {
+        let address = serve_once(|_| b"-ERR\r\n".to_vec()).await;
+
+        let start = Instant::now();
+
+        // Notice: the peer holds the connection open, thus the timeout ends the exchange
+        assert!(proceed_replica_probe_poll_tcp_address(
+            &address,
+            Duration::from_millis(500),
+            &Some("PING\r\n".to_string()),
+            &response_match("^\\+PONG"),
+        )
+        .await
+        .is_none());
+
+        assert!(start.elapsed() < TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn tcp_expect_response_capped() {
+        // The response only matches past the maximum response size, thus it never matches
+        let address = serve_once(|_| {
+            let mut response = vec![b'.'; PROBE_TCP_RESPONSE_MAXIMUM_BYTES];
+
+            response.extend_from_slice(b"READY");
+            response
+        })
+        .await;
+
+        let start = Instant::now();
+
+        assert!(proceed_replica_probe_poll_tcp_address(
+            &address,
+            TIMEOUT,
+            &Some("HELLO\r\n".to_string()),
+            &response_match("READY"),
+        )
+        .await
+        .is_none());
+
+        // Reading stopped at the cap (rather than waiting for the timeout)
+        assert!(start.elapsed() < TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn tcp_timeout_covers_exchange() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // The server trickles a non-matching response, each read completing within the timeout
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            for _ in 0..50 {
+                sleep(Duration::from_millis(200)).await;
+
+                if stream.write_all(b".").await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let start = Instant::now();
+
+        assert!(proceed_replica_probe_poll_tcp_address(
+            &address,
+            Duration::from_millis(500),
+            &None,
+            &response_match("PONG"),
+        )
+        .await
+        .is_none());
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn tcp_connect_only() {
+        let address = serve_once(|_| Vec::new()).await;
+
+        assert!(
+            proceed_replica_probe_poll_tcp_address(&address, TIMEOUT, &None, &None)
+                .await
+                .is_some()
+        );
     }
 }
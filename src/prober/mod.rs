@@ -15,9 +15,23 @@
 // Copyright: 2018, Valerian Saliou <valerian@valeriansaliou.name>
 // License: Mozilla Public License v2.0 (MPL v2.0)
 
-mod replica;
+mod dns;
+mod grpc;
+mod http;
+mod icmp;
+mod protocol;
+mod schedule;
+mod script;
+mod sql;
+mod tls;
+mod udp;
+mod websocket;
 
 pub mod manager;
 pub mod mode;
+pub mod persist;
+pub mod rabbitmq;
+pub mod replica;
+pub mod report;
 pub mod states;
 pub mod status;
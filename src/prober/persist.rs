@@ -0,0 +1,435 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use super::manager::{Store, STORE as PROBER_STORE};
+use super::mode::Mode;
+use super::states::{
+    ServiceStatesNotifier, ServiceStatesProbeNodeReplica, ServiceStatesProbeNodeReplicaLoad,
+    ServiceStatesProbeNodeReplicaMetrics, ServiceStatesProbeNodeReplicaReport,
+};
+use super::status::Status;
+use crate::APP_CONF;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("could not access state file: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("could not (de)serialize state file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedStore {
+    status: Status,
+    notified: Option<SystemTime>,
+    notifier: ServiceStatesNotifier,
+    replicas: IndexMap<String, IndexMap<String, IndexMap<String, PersistedReplica>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedReplica {
+    status: Status,
+    metrics: ServiceStatesProbeNodeReplicaMetrics,
+    load: Option<ServiceStatesProbeNodeReplicaLoad>,
+    report: Option<ServiceStatesProbeNodeReplicaReport>,
+    #[serde(default)]
+    message: Option<String>,
+    checked: Option<SystemTime>,
+}
+
+pub fn save() {
+    if let Some(ref state) = APP_CONF.load().state {
+        match save_to(&state.path) {
+            Ok(()) => tracing::debug!("saved prober state to: {:?}", state.path),
+            Err(err) => tracing::error!(
+                "could not save prober state to: {:?} (error: {})",
+                state.path,
+                err
+            ),
+        }
+    }
+}
+
+pub fn restore() {
+    if let Some(ref state) = APP_CONF.load().state {
+        // Notice: a missing state file is expected upon first boot, so this is not an error
+        if !state.path.exists() {
+            tracing::info!("no prober state to restore from: {:?}", state.path);
+
+            return;
+        }
+
+        match restore_from(&state.path) {
+            Ok(()) => tracing::info!("restored prober state from: {:?}", state.path),
+            Err(err) => tracing::error!(
+                "could not restore prober state from: {:?} (error: {})",
+                state.path,
+                err
+            ),
+        }
+    }
+}
+
+impl From<&Store> for PersistedStore {
+    fn from(store: &Store) -> Self {
+        PersistedStore {
+            status: store.states.status.clone(),
+            notified: store.notified,
+            notifier: store.states.notifier.clone(),
+            replicas: store
+                .states
+                .probes
+                .iter()
+                .map(|(probe_id, probe)| {
+                    let nodes = probe
+                        .nodes
+                        .iter()
+                        .map(|(node_id, node)| {
+                            let replicas = node
+                                .replicas
+                                .iter()
+                                .map(|(replica_id, replica)| {
+                                    (
+                                        replica_id.clone(),
+                                        PersistedReplica {
+                                            status: replica.status.clone(),
+                                            metrics: replica.metrics.clone(),
+                                            load: replica.load.clone(),
+                                            report: replica.report.clone(),
+                                            message: replica.message.clone(),
+                                            checked: replica.checked,
+                                        },
+                                    )
+                                })
+                                .collect();
+
+                            (node_id.clone(), replicas)
+                        })
+                        .collect();
+
+                    (probe_id.clone(), nodes)
+                })
+                .collect(),
+        }
+    }
+}
+
+fn save_to(path: &Path) -> Result<(), Error> {
+    // Copy persisted values from store (release the lock before accessing the file system)
+    let persisted = PersistedStore::from(&*PROBER_STORE.read());
+
+    write_to(path, &persisted)
+}
+
+fn write_to(path: &Path, persisted: &PersistedStore) -> Result<(), Error> {
+    // Write to a temporary file first, then swap it with the state file; this ensures that the \
+    //   state file never ends up truncated if the process gets killed while writing it.
+    let temporary_path = path.with_extension("tmp");
+
+    fs::write(&temporary_path, serde_json::to_vec(persisted)?)?;
+    fs::rename(&temporary_path, path)?;
+
+    Ok(())
+}
+
+fn restore_from(path: &Path) -> Result<(), Error> {
+    let persisted = read_from(path)?;
+
+    persisted.restore(&mut PROBER_STORE.write());
+
+    Ok(())
+}
+
+fn read_from(path: &Path) -> Result<PersistedStore, Error> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+impl PersistedStore {
+    fn restore(self, store: &mut Store) {
+        store.states.status = self.status;
+        store.states.notifier = self.notifier;
+        store.notified = self.notified;
+
+        // Restore replicas that still exist in configuration (services or nodes might have been \
+        //   removed since the state was saved)
+        for (probe_id, nodes) in self.replicas {
+            if let Some(probe) = store.states.probes.get_mut(&probe_id) {
+                for (node_id, replicas) in nodes {
+                    if let Some(node) = probe.nodes.get_mut(&node_id) {
+                        for (replica_id, persisted_replica) in replicas {
+                            if let Some(replica) = node.replicas.get_mut(&replica_id) {
+                                // Configured replica (ie. 'poll' or 'script' nodes)
+                                replica.status = persisted_replica.status;
+                                replica.metrics = persisted_replica.metrics;
+                                replica.message = persisted_replica.message;
+                                replica.checked = persisted_replica.checked;
+                            } else if node.mode == Mode::Push || node.mode == Mode::Local {
+                                // Reported replica (ie. 'push' or 'local' nodes)
+                                node.replicas.insert(
+                                    replica_id,
+                                    ServiceStatesProbeNodeReplica {
+                                        status: persisted_replica.status,
+                                        url: None,
+                                        script: None,
+                                        metrics: persisted_replica.metrics,
+                                        load: persisted_replica.load,
+                                        report: persisted_replica.report,
+                                        message: persisted_replica.message,
+                                        checked: persisted_replica.checked,
+                                    },
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::super::manager::make_probes;
+    use super::super::states::{
+        ServiceStates, ServiceStatesNotifierAcknowledgement, ServiceStatesProbeNodeReplicaLoadQueue,
+    };
+    use super::*;
+    use crate::config::Config;
+
+    const CONFIG_BASE: &str = include_str!("../../tests/fixtures/overvakt.toml");
+
+    fn make_store(probes: &str) -> Store {
+        let conf = Config::parse(&format!("{}\n{}", CONFIG_BASE, probes)).unwrap();
+
+        Store {
+            states: ServiceStates {
+                status: Status::Healthy,
+                date: None,
+                probes: make_probes(&conf).unwrap(),
+                notifier: ServiceStatesNotifier {
+                    reminder_backoff_counter: 1,
+                    reminder_ignore_until: None,
+                    acknowledgement: None,
+                },
+            },
+            notified: None,
+            generation: 0,
+        }
+    }
+
+    fn replica<'a>(
+        store: &'a Store,
+        node_id: &str,
+        replica_id: &str,
+    ) -> Option<&'a ServiceStatesProbeNodeReplica> {
+        store.states.probes["persist"].nodes[node_id]
+            .replicas
+            .get(replica_id)
+    }
+
+    #[test]
+    fn save_and_restore_unchanged_replicas() {
+        let mut saved_store = make_store(
+            r#"
+            [probe.service.persist]
+            label = "Persisted"
+
+            [probe.service.persist.node.poll]
+            label = "Poll"
+            mode = "poll"
+            replicas = ["tcp://kept.example.com:80", "tcp://removed.example.com:80"]
+
+            [probe.service.persist.node.push]
+            label = "Push"
+            mode = "push"
+            "#,
+        );
+
+        // Change states (as if probes and reports happened)
+        let now = SystemTime::now();
+
+        saved_store.states.status = Status::Dead;
+        saved_store.states.notifier.acknowledgement = Some(ServiceStatesNotifierAcknowledgement {
+            by: "on-call".to_string(),
+            time: now,
+        });
+        saved_store.notified = Some(now);
+
+        {
+            let node = saved_store.states.probes["persist"]
+                .nodes
+                .get_mut("poll")
+                .unwrap();
+
+            for replica in node.replicas.values_mut() {
+                replica.status = Status::Dead;
+                replica.message = Some("tcp: connection refused".to_string());
+                replica.checked = Some(now);
+            }
+
+            let node = saved_store.states.probes["persist"]
+                .nodes
+                .get_mut("push")
+                .unwrap();
+
+            node.replicas.insert(
+                "reporter".to_string(),
+                ServiceStatesProbeNodeReplica {
+                    status: Status::Sick,
+                    url: None,
+                    script: None,
+                    metrics: ServiceStatesProbeNodeReplicaMetrics::default(),
+                    load: Some(ServiceStatesProbeNodeReplicaLoad {
+                        cpu: 0.95,
+                        ram: 0.5,
+                        queue: ServiceStatesProbeNodeReplicaLoadQueue::default(),
+                    }),
+                    report: Some(ServiceStatesProbeNodeReplicaReport {
+                        time: now,
+                        interval: Duration::from_secs(30),
+                    }),
+                    message: None,
+                    checked: Some(now),
+                },
+            );
+        }
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("state.json");
+
+        write_to(&path, &PersistedStore::from(&saved_store)).unwrap();
+
+        // Restore into a store whose configuration changed in the meantime
+        let mut restored_store = make_store(
+            r#"
+            [probe.service.persist]
+            label = "Persisted"
+
+            [probe.service.persist.node.poll]
+            label = "Poll"
+            mode = "poll"
+            replicas = ["tcp://kept.example.com:80", "tcp://added.example.com:80"]
+
+            [probe.service.persist.node.push]
+            label = "Push"
+            mode = "push"
+            "#,
+        );
+
+        read_from(&path).unwrap().restore(&mut restored_store);
+
+        assert_eq!(restored_store.states.status, Status::Dead);
+        assert_eq!(restored_store.notified, Some(now));
+        assert_eq!(
+            restored_store
+                .states
+                .notifier
+                .acknowledgement
+                .as_ref()
+                .map(|acknowledgement| acknowledgement.by.as_str()),
+            Some("on-call")
+        );
+
+        // Unchanged replica gets its state back
+        let kept = replica(&restored_store, "poll", "tcp://kept.example.com:80").unwrap();
+
+        assert_eq!(kept.status, Status::Dead);
+        assert_eq!(kept.message.as_deref(), Some("tcp: connection refused"));
+        assert_eq!(kept.checked, Some(now));
+
+        // Added replica starts afresh, while removed replica is not restored
+        let added = replica(&restored_store, "poll", "tcp://added.example.com:80").unwrap();
+
+        assert_eq!(added.status, Status::Healthy);
+        assert!(added.message.is_none() && added.checked.is_none());
+
+        assert!(replica(&restored_store, "poll", "tcp://removed.example.com:80").is_none());
+
+        // Reported replica is restored as-is
+        let reporter = replica(&restored_store, "push", "reporter").unwrap();
+
+        assert_eq!(reporter.status, Status::Sick);
+        assert_eq!(reporter.load.as_ref().map(|load| load.cpu), Some(0.95));
+        assert_eq!(
+            reporter.report.as_ref().map(|report| report.interval),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn reported_replicas_are_not_restored_into_poll_nodes() {
+        let mut saved_store = make_store(
+            r#"
+            [probe.service.persist]
+            label = "Persisted"
+
+            [probe.service.persist.node.switched]
+            label = "Switched"
+            mode = "local"
+            "#,
+        );
+
+        saved_store.states.probes["persist"]
+            .nodes
+            .get_mut("switched")
+            .unwrap()
+            .replicas
+            .insert(
+                "reporter".to_string(),
+                ServiceStatesProbeNodeReplica {
+                    status: Status::Dead,
+                    url: None,
+                    script: None,
+                    metrics: ServiceStatesProbeNodeReplicaMetrics::default(),
+                    load: None,
+                    report: None,
+                    message: None,
+                    checked: None,
+                },
+            );
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("state.json");
+
+        write_to(&path, &PersistedStore::from(&saved_store)).unwrap();
+
+        // Node was switched to another mode since the state was saved
+        let mut restored_store = make_store(
+            r#"
+            [probe.service.persist]
+            label = "Persisted"
+
+            [probe.service.persist.node.switched]
+            label = "Switched"
+            mode = "poll"
+            replicas = ["tcp://switched.example.com:80"]
+            "#,
+        );
+
+        read_from(&path).unwrap().restore(&mut restored_store);
+
+        assert!(replica(&restored_store, "switched", "reporter").is_none());
+        assert_eq!(
+            replica(&restored_store, "switched", "tcp://switched.example.com:80")
+                .map(|replica| replica.status.clone()),
+            Some(Status::Healthy)
+        );
+    }
+}
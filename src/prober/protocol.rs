@@ -0,0 +1,421 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout as with_timeout;
+
+use super::replica::ReplicaProtocol;
+use super::status::Status;
+use crate::APP_CONF;
+
+const PROTOCOL_LINE_MAXIMUM_BYTES: u64 = 1024;
+const PROTOCOL_REPLY_MAXIMUM_LINES: usize = 64;
+const SSH_BANNER_MAXIMUM_LINES: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("could not resolve any address")]
+    NoAddress,
+
+    #[error("connection failed: {0}")]
+    Connect(#[source] io::Error),
+
+    #[error("exchange failed: {0}")]
+    Exchange(#[from] io::Error),
+
+    #[error("connection closed by server")]
+    Closed,
+
+    #[error("unexpected reply: {0}")]
+    UnexpectedReply(String),
+}
+
+type Stream = BufReader<TcpStream>;
+
+pub async fn proceed_replica_probe_poll_protocol(
+    protocol: &ReplicaProtocol,
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> (Status, Option<Duration>, Option<String>) {
+    tracing::debug!(
+        "prober poll will fire for {} target: {}:{}",
+        protocol.name(),
+        host,
+        port
+    );
+
+    // Acquire exchange start time (used for latency calculation, which includes connecting)
+    let exchange_start_time = SystemTime::now();
+
+    match with_timeout(timeout, exchange(protocol, host, port)).await {
+        Ok(Ok(())) => (
+            Status::Healthy,
+            SystemTime::now().duration_since(exchange_start_time).ok(),
+            None,
+        ),
+        Ok(Err(err)) => {
+            tracing::debug!(
+                "prober poll error for {} target: {}:{} (error: {})",
+                protocol.name(),
+                host,
+                port,
+                err
+            );
+
+            (
+                Status::Dead,
+                None,
+                Some(format!("{}: {}", protocol.name(), err)),
+            )
+        }
+        Err(_) => {
+            tracing::debug!(
+                "prober poll timed out for {} target: {}:{}",
+                protocol.name(),
+                host,
+                port
+            );
+
+            (
+                Status::Dead,
+                None,
+                Some(format!(
+                    "{}: timed out after {}s",
+                    protocol.name(),
+                    timeout.as_secs()
+                )),
+            )
+        }
+    }
+}
+
+async fn exchange(protocol: &ReplicaProtocol, host: &str, port: u16) -> Result<(), Error> {
+    let address = lookup_host((host, port))
+        .await
+        .map_err(Error::Connect)?
+        .next()
+        .ok_or(Error::NoAddress)?;
+
+    let mut stream = BufReader::new(TcpStream::connect(address).await.map_err(Error::Connect)?);
+
+    match protocol {
+        ReplicaProtocol::Redis(username, password) => {
+            exchange_redis(&mut stream, username, password).await
+        }
+        ReplicaProtocol::Memcached => exchange_memcached(&mut stream).await,
+        ReplicaProtocol::Smtp => exchange_smtp(&mut stream).await,
+        ReplicaProtocol::Ssh => exchange_ssh(&mut stream).await,
+    }
+}
+
+async fn exchange_redis(
+    stream: &mut Stream,
+    username: &Option<String>,
+    password: &Option<String>,
+) -> Result<(), Error> {
+    // Authenticate first? (the username is only supported by Redis 6 and above)
+    if let Some(password) = password {
+        let mut command = vec!["AUTH"];
+
+        if let Some(username) = username {
+            command.push(username);
+        }
+
+        command.push(password);
+
+        send(stream, &encode_redis_command(&command)).await?;
+
+        let reply = read_line(stream).await?;
+
+        if reply != "+OK" {
+            return Err(Error::UnexpectedReply(reply));
+        }
+    }
+
+    send(stream, &encode_redis_command(&["PING"])).await?;
+
+    let reply = read_line(stream).await?;
+
+    if reply != "+PONG" {
+        return Err(Error::UnexpectedReply(reply));
+    }
+
+    Ok(())
+}
+
+async fn exchange_memcached(stream: &mut Stream) -> Result<(), Error> {
+    send(stream, b"version\r\n").await?;
+
+    let reply = read_line(stream).await?;
+
+    if !reply.starts_with("VERSION ") {
+        return Err(Error::UnexpectedReply(reply));
+    }
+
+    Ok(())
+}
+
+async fn exchange_smtp(stream: &mut Stream) -> Result<(), Error> {
+    // Wait for the server greeting, before introducing ourselves
+    read_smtp_reply(stream, "220").await?;
+
+    // Notice: the EHLO domain is taken from the status page URL, which identifies this client
+    let domain = APP_CONF
+        .load()
+        .branding
+        .page_url
+        .host_str()
+        .unwrap_or("localhost")
+        .to_string();
+
+    send(stream, format!("EHLO {}\r\n", domain).as_bytes()).await?;
+
+    read_smtp_reply(stream, "250").await?;
+
+    // Notice: the session is closed on a best-effort basis, as it is not required
+    if send(stream, b"QUIT\r\n").await.is_ok() {
+        let _ = read_smtp_reply(stream, "221").await;
+    }
+
+    Ok(())
+}
+
+async fn exchange_ssh(stream: &mut Stream) -> Result<(), Error> {
+    // Notice: servers may send other lines before their identification string, which is the \
+    //   first line starting with 'SSH-' (see RFC 4253, section 4.2)
+    for _ in 0..SSH_BANNER_MAXIMUM_LINES {
+        let line = read_line(stream).await?;
+
+        if line.starts_with("SSH-") {
+            if line.starts_with("SSH-2.0-") || line.starts_with("SSH-1.99-") {
+                return Ok(());
+            }
+
+            return Err(Error::UnexpectedReply(line));
+        }
+    }
+
+    Err(Error::UnexpectedReply(
+        "no ssh identification string".to_string(),
+    ))
+}
+
+async fn read_smtp_reply(stream: &mut Stream, expected_code: &str) -> Result<(), Error> {
+    // Notice: multi-line replies have a '-' following the code on all lines but the last one, \
+    //   which has a space (or nothing) instead.
+    for _ in 0..PROTOCOL_REPLY_MAXIMUM_LINES {
+        let line = read_line(stream).await?;
+
+        if !line.starts_with(expected_code) {
+            return Err(Error::UnexpectedReply(line));
+        }
+
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+
+    Err(Error::UnexpectedReply("reply is too long".to_string()))
+}
+
+async fn send(stream: &mut Stream, data: &[u8]) -> Result<(), Error> {
+    stream.get_mut().write_all(data).await?;
+
+    Ok(())
+}
+
+async fn read_line(stream: &mut Stream) -> Result<String, Error> {
+    let mut line = Vec::new();
+
+    (&mut *stream)
+        .take(PROTOCOL_LINE_MAXIMUM_BYTES)
+        .read_until(b'\n', &mut line)
+        .await?;
+
+    if line.is_empty() {
+        return Err(Error::Closed);
+    }
+
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+fn encode_redis_command(arguments: &[&str]) -> Vec<u8> {
+    // Commands are sent as arrays of bulk strings (see the Redis serialization protocol)
+    let mut command = format!("*{}\r\n", arguments.len()).into_bytes();
+
+    for argument in arguments {
+        command.extend_from_slice(format!("${}\r\n", argument.len()).as_bytes());
+        command.extend_from_slice(argument.as_bytes());
+        command.extend_from_slice(b"\r\n");
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Plays given script on the first accepted connection: each step waits for a request \
+    ///   containing the expected text (if any), then replies.
+    async fn serve(script: Vec<(Option<&'static str>, &'static str)>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            for (expected, reply) in script {
+                if let Some(expected) = expected {
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+
+                    while !String::from_utf8_lossy(&request).contains(expected) {
+                        let read = stream.read(&mut buffer).await.unwrap();
+
+                        assert!(read > 0, "request ended early, expected: {}", expected);
+
+                        request.extend_from_slice(&buffer[..read]);
+                    }
+                }
+
+                stream.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+
+        port
+    }
+
+    async fn probe(protocol: ReplicaProtocol, port: u16) -> (Status, Option<String>) {
+        let (status, _, message) =
+            proceed_replica_probe_poll_protocol(&protocol, "127.0.0.1", port, TIMEOUT).await;
+
+        (status, message)
+    }
+
+    fn redis(password: Option<&str>) -> ReplicaProtocol {
+        ReplicaProtocol::Redis(None, password.map(str::to_string))
+    }
+
+    #[test]
+    fn redis_command_encoding() {
+        assert_eq!(
+            encode_redis_command(&["AUTH", "user", "secret"]),
+            b"*3\r\n$4\r\nAUTH\r\n$4\r\nuser\r\n$6\r\nsecret\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn redis_auth_then_pong() {
+        let port = serve(vec![
+            (Some("$4\r\nAUTH\r\n$6\r\nsecret\r\n"), "+OK\r\n"),
+            (Some("$4\r\nPING\r\n"), "+PONG\r\n"),
+        ])
+        .await;
+
+        assert_eq!(probe(redis(Some("secret")), port).await.0, Status::Healthy);
+    }
+
+    #[tokio::test]
+    async fn redis_wrong_password() {
+        let port = serve(vec![(
+            Some("AUTH"),
+            "-WRONGPASS invalid username-password pair or user is disabled.\r\n",
+        )])
+        .await;
+
+        let (status, message) = probe(redis(Some("wrong")), port).await;
+
+        assert_eq!(status, Status::Dead);
+        assert!(message.unwrap().contains("-WRONGPASS"));
+    }
+
+    #[tokio::test]
+    async fn redis_without_auth() {
+        let port = serve(vec![(Some("PING"), "+PONG\r\n")]).await;
+
+        assert_eq!(probe(redis(None), port).await.0, Status::Healthy);
+    }
+
+    #[tokio::test]
+    async fn memcached_version() {
+        let port = serve(vec![(Some("version\r\n"), "VERSION 1.6.17\r\n")]).await;
+
+        assert_eq!(
+            probe(ReplicaProtocol::Memcached, port).await.0,
+            Status::Healthy
+        );
+
+        let port = serve(vec![(Some("version\r\n"), "ERROR\r\n")]).await;
+
+        assert_eq!(
+            probe(ReplicaProtocol::Memcached, port).await.0,
+            Status::Dead
+        );
+    }
+
+    #[tokio::test]
+    async fn smtp_multi_line_reply() {
+        let port = serve(vec![
+            (None, "220 mail.example.com ESMTP\r\n"),
+            (
+                Some("EHLO status.example.com\r\n"),
+                "250-mail.example.com\r\n250-PIPELINING\r\n250-SIZE 10240000\r\n250 8BITMIME\r\n",
+            ),
+            (Some("QUIT\r\n"), "221 Bye\r\n"),
+        ])
+        .await;
+
+        assert_eq!(probe(ReplicaProtocol::Smtp, port).await.0, Status::Healthy);
+    }
+
+    #[tokio::test]
+    async fn smtp_rejected_greeting() {
+        let port = serve(vec![(None, "554 5.7.1 Service unavailable\r\n")]).await;
+
+        let (status, message) = probe(ReplicaProtocol::Smtp, port).await;
+
+        assert_eq!(status, Status::Dead);
+        assert!(message.unwrap().contains("554 5.7.1"));
+    }
+
+    #[tokio::test]
+    async fn ssh_banner_after_other_lines() {
+        let port = serve(vec![(
+            None,
+            "Welcome to the bastion\r\nSSH-2.0-OpenSSH_9.0\r\n",
+        )])
+        .await;
+
+        assert_eq!(probe(ReplicaProtocol::Ssh, port).await.0, Status::Healthy);
+
+        let port = serve(vec![(None, "SSH-1.5-legacy\r\n")]).await;
+
+        assert_eq!(probe(ReplicaProtocol::Ssh, port).await.0, Status::Dead);
+    }
+
+    #[tokio::test]
+    async fn closed_connection() {
+        let port = serve(Vec::new()).await;
+
+        let (status, message) = probe(ReplicaProtocol::Ssh, port).await;
+
+        assert_eq!(status, Status::Dead);
+        assert_eq!(message.as_deref(), Some("ssh: connection closed by server"));
+    }
+}
@@ -0,0 +1,385 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// ---
+//
+// Fork of: Vigil
+//
+// Microservices Status Page
+// Copyright: 2018, Valerian Saliou <valerian@valeriansaliou.name>
+// License: Mozilla Public License v2.0 (MPL v2.0)
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::time::sleep;
+
+use super::manager::STORE as PROBER_STORE;
+use super::states::{ServiceStatesProbeNodeRabbitMQ, ServiceStatesProbeNodeReplicaMetricsRabbitMQ};
+use crate::config::plugins::PluginRabbitMQ;
+use crate::APP_CONF;
+
+// Notice: clients are cached per timeout, as the dead delay might change upon reloading the \
+//   configuration.
+static RABBITMQ_HTTP_CLIENTS: Lazy<RwLock<HashMap<Duration, Client>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+#[derive(Deserialize)]
+struct RabbitMQAPIQueueResponse {
+    messages_ready: u32,
+    messages_unacknowledged: u32,
+}
+
+struct QueueProbeResult {
+    loaded: bool,
+    stalled: bool,
+    metrics: Option<ServiceStatesProbeNodeReplicaMetricsRabbitMQ>,
+}
+
+fn acquire_client(timeout: Duration) -> Client {
+    if let Some(client) = RABBITMQ_HTTP_CLIENTS.read().get(&timeout) {
+        return client.clone();
+    }
+
+    let client = Client::builder()
+        .timeout(timeout)
+        .gzip(false)
+        .build()
+        .unwrap();
+
+    RABBITMQ_HTTP_CLIENTS
+        .write()
+        .insert(timeout, client.clone());
+
+    client
+}
+
+async fn proceed_rabbitmq_queue_probe(
+    rabbitmq: &PluginRabbitMQ,
+    node_rabbitmq: &ServiceStatesProbeNodeRabbitMQ,
+    timeout: Duration,
+) -> QueueProbeResult {
+    let mut result = QueueProbeResult {
+        loaded: false,
+        stalled: false,
+        metrics: None,
+    };
+
+    // Node-level thresholds override the global thresholds (if set)
+    let queue_nack_healthy_below = node_rabbitmq
+        .queue_nack_healthy_below
+        .unwrap_or(rabbitmq.queue_nack_healthy_below);
+    let queue_nack_dead_above = node_rabbitmq
+        .queue_nack_dead_above
+        .unwrap_or(rabbitmq.queue_nack_dead_above);
+
+    // Generate request URL (the virtual host and queue are percent-encoded as path segments, \
+    //   which is required for eg. the default '/' virtual host)
+    let mut request_url = rabbitmq.api_url.clone();
+
+    if let Ok(mut segments) = request_url.path_segments_mut() {
+        segments.pop_if_empty().extend([
+            "api",
+            "queues",
+            &rabbitmq.virtualhost,
+            &node_rabbitmq.queue,
+        ]);
+    }
+
+    tracing::debug!(
+        "prober rabbitmq will fire for queue: {} at url: {}",
+        node_rabbitmq.queue,
+        request_url
+    );
+
+    let response = match acquire_client(timeout)
+        .get(request_url.as_str())
+        .basic_auth(&rabbitmq.auth_username, Some(&rabbitmq.auth_password))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+    {
+        Ok(response) => response.json::<RabbitMQAPIQueueResponse>().await,
+        Err(err) => Err(err),
+    };
+
+    match response {
+        Ok(queue) => {
+            tracing::debug!(
+                "prober rabbitmq result received for queue: {} (ready: {}, nack: {})",
+                node_rabbitmq.queue,
+                queue.messages_ready,
+                queue.messages_unacknowledged
+            );
+
+            // Queue is stalled? (or is it only loaded?)
+            if queue.messages_ready > rabbitmq.queue_ready_dead_above
+                || queue.messages_unacknowledged > queue_nack_dead_above
+            {
+                result.stalled = true;
+            } else if queue.messages_ready >= rabbitmq.queue_ready_healthy_below
+                || queue.messages_unacknowledged >= queue_nack_healthy_below
+            {
+                result.loaded = true;
+            }
+
+            result.metrics = Some(ServiceStatesProbeNodeReplicaMetricsRabbitMQ {
+                queue_ready: queue.messages_ready,
+                queue_nack: queue.messages_unacknowledged,
+            });
+        }
+        Err(err) => {
+            tracing::warn!(
+                "prober rabbitmq result was not received for queue: {} (error: {})",
+                node_rabbitmq.queue,
+                err
+            );
+        }
+    }
+
+    result
+}
+
+async fn probe_queue(
+    rabbitmq: &PluginRabbitMQ,
+    node_rabbitmq: &ServiceStatesProbeNodeRabbitMQ,
+    timeout: Duration,
+) -> QueueProbeResult {
+    let result = proceed_rabbitmq_queue_probe(rabbitmq, node_rabbitmq, timeout).await;
+
+    // Check once again? (the queue is seen as loaded, and systems might still be processing \
+    //   pending queue payloads)
+    if result.loaded {
+        if let Some(retry_delay) = rabbitmq.queue_loaded_retry_delay {
+            tracing::debug!(
+                "prober rabbitmq queue: {} is loaded, will check again in {}ms",
+                node_rabbitmq.queue,
+                retry_delay
+            );
+
+            sleep(Duration::from_millis(retry_delay)).await;
+
+            return proceed_rabbitmq_queue_probe(rabbitmq, node_rabbitmq, timeout).await;
+        }
+    }
+
+    result
+}
+
+pub async fn dispatch(probe_id: &str, node_id: &str, replica_id: &str) {
+    let conf = APP_CONF.load_full();
+
+    let rabbitmq = match conf.plugins.rabbitmq {
+        Some(ref rabbitmq) => rabbitmq,
+        None => return,
+    };
+
+    // Acquire queue settings for node (if any; release the lock before probing the queue)
+    let node_rabbitmq = {
+        let store = PROBER_STORE.read();
+
+        store
+            .states
+            .probes
+            .get(probe_id)
+            .and_then(|probe| probe.nodes.get(node_id))
+            .and_then(|node| node.rabbitmq.clone())
+    };
+
+    if let Some(node_rabbitmq) = node_rabbitmq {
+        let result = probe_queue(
+            rabbitmq,
+            &node_rabbitmq,
+            Duration::from_secs(conf.metrics.poll_delay_dead),
+        )
+        .await;
+
+        // Update replica queue load and metrics (write-lock the store)
+        let mut store = PROBER_STORE.write();
+
+        if let Some(ref mut probe) = store.states.probes.get_mut(probe_id) {
+            if let Some(ref mut node) = probe.nodes.get_mut(node_id) {
+                if let Some(ref mut replica) = node.replicas.get_mut(replica_id) {
+                    if let Some(ref mut replica_load) = replica.load {
+                        replica_load.queue.loaded = result.loaded;
+                        replica_load.queue.stalled = result.stalled;
+                    }
+
+                    replica.metrics.rabbitmq = result.metrics;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+    use url::Url;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Serves each queue response `(ready, nack)` to a request, then returns the requested paths.
+    async fn serve_queue(responses: Vec<(u32, u32)>) -> (Url, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut paths = Vec::new();
+
+            for (ready, nack) in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                // Read request head (requests have no body)
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buffer).await.unwrap();
+
+                    assert!(read > 0, "request ended early");
+
+                    request.extend_from_slice(&buffer[..read]);
+                }
+
+                let request = String::from_utf8(request).unwrap();
+
+                // Authenticated as 'guest:guest'
+                assert!(request.contains("authorization: Basic Z3Vlc3Q6Z3Vlc3Q=\r\n"));
+
+                paths.push(request.split(' ').nth(1).unwrap().to_string());
+
+                let body = format!(
+                    r#"{{"name":"jobs","messages_ready":{},"messages_unacknowledged":{}}}"#,
+                    ready, nack
+                );
+
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+
+            paths
+        });
+
+        (url, server)
+    }
+
+    fn make_plugin(api_url: Url, queue_loaded_retry_delay: Option<u64>) -> PluginRabbitMQ {
+        PluginRabbitMQ {
+            api_url,
+            auth_username: "guest".to_string(),
+            auth_password: "guest".to_string(),
+            virtualhost: "/".to_string(),
+            queue_ready_healthy_below: 500,
+            queue_nack_healthy_below: 100,
+            queue_ready_dead_above: 20000,
+            queue_nack_dead_above: 5000,
+            queue_loaded_retry_delay,
+        }
+    }
+
+    fn make_node(queue_nack_dead_above: Option<u32>) -> ServiceStatesProbeNodeRabbitMQ {
+        ServiceStatesProbeNodeRabbitMQ {
+            queue: "jobs".to_string(),
+            queue_nack_healthy_below: None,
+            queue_nack_dead_above,
+        }
+    }
+
+    async fn probe(
+        rabbitmq: PluginRabbitMQ,
+        node_rabbitmq: ServiceStatesProbeNodeRabbitMQ,
+    ) -> QueueProbeResult {
+        probe_queue(&rabbitmq, &node_rabbitmq, TIMEOUT).await
+    }
+
+    #[tokio::test]
+    async fn healthy_queue() {
+        let (url, server) = serve_queue(vec![(10, 5)]).await;
+
+        let result = probe(make_plugin(url, Some(10)), make_node(None)).await;
+
+        assert!(!result.loaded && !result.stalled);
+
+        let metrics = result.metrics.expect("no queue metrics");
+
+        assert_eq!((metrics.queue_ready, metrics.queue_nack), (10, 5));
+
+        // The default virtual host is percent-encoded as a path segment
+        assert_eq!(server.await.unwrap(), ["/api/queues/%2F/jobs"]);
+    }
+
+    #[tokio::test]
+    async fn nack_above_dead_threshold() {
+        // The node-level dead threshold overrides the global one
+        let (url, server) = serve_queue(vec![(10, 80)]).await;
+
+        let result = probe(make_plugin(url, Some(10)), make_node(Some(50))).await;
+
+        assert!(result.stalled && !result.loaded);
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn loaded_queue_is_checked_again() {
+        // Loaded queue gets stalled in the meantime
+        let (url, server) = serve_queue(vec![(600, 5), (30000, 5)]).await;
+
+        let result = probe(make_plugin(url, Some(10)), make_node(None)).await;
+
+        assert!(result.stalled && !result.loaded);
+        assert_eq!(server.await.unwrap().len(), 2);
+
+        // Loaded queue gets processed in the meantime
+        let (url, server) = serve_queue(vec![(600, 5), (20, 5)]).await;
+
+        let result = probe(make_plugin(url, Some(10)), make_node(None)).await;
+
+        assert!(!result.stalled && !result.loaded);
+        assert_eq!(result.metrics.map(|metrics| metrics.queue_ready), Some(20));
+        assert_eq!(server.await.unwrap().len(), 2);
+
+        // Loaded queue is not checked again without a retry delay
+        let (url, server) = serve_queue(vec![(600, 5)]).await;
+
+        let result = probe(make_plugin(url, None), make_node(None)).await;
+
+        assert!(result.loaded && !result.stalled);
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_api() {
+        let (url, server) = serve_queue(Vec::new()).await;
+
+        server.await.unwrap();
+
+        let result = probe(make_plugin(url, Some(10)), make_node(None)).await;
+
+        assert!(!result.loaded && !result.stalled && result.metrics.is_none());
+    }
+}
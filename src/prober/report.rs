@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// ---
+//
+// Fork of: Vigil
+//
+// Microservices Status Page
+// Copyright: 2018, Valerian Saliou <valerian@valeriansaliou.name>
+// License: Mozilla Public License v2.0 (MPL v2.0)
+
+use std::time::{Duration, SystemTime};
+
+use super::manager::STORE as PROBER_STORE;
+use super::mode::Mode;
+use super::states::{
+    ServiceStatesProbeNodeReplica, ServiceStatesProbeNodeReplicaLoad,
+    ServiceStatesProbeNodeReplicaLoadQueue, ServiceStatesProbeNodeReplicaMetrics,
+    ServiceStatesProbeNodeReplicaMetricsSystem, ServiceStatesProbeNodeReplicaReport,
+};
+use super::status::Status;
+
+#[derive(Debug)]
+pub enum HandleLoadError {
+    InvalidLoad,
+    WrongMode,
+    NotFound,
+}
+
+#[derive(Debug)]
+pub enum HandleHealthError {
+    WrongMode,
+    NotFound,
+}
+
+#[derive(Debug)]
+pub enum HandleFlushError {
+    WrongMode,
+    NotFound,
+}
+
+pub fn handle_load(
+    probe_id: &str,
+    node_id: &str,
+    replica_id: &str,
+    interval: u64,
+    load_cpu: f32,
+    load_ram: f32,
+) -> Result<(), HandleLoadError> {
+    tracing::debug!(
+        "load report handle: {}:{}:{} (cpu: {}, ram: {})",
+        probe_id,
+        node_id,
+        replica_id,
+        load_cpu,
+        load_ram
+    );
+
+    // Validate loads (negative values are invalid; also reject NaN values)
+    if !(load_cpu >= 0.0 && load_ram >= 0.0) {
+        return Err(HandleLoadError::InvalidLoad);
+    }
+
+    let mut store = PROBER_STORE.write();
+
+    if let Some(ref mut probe) = store.states.probes.get_mut(probe_id) {
+        if let Some(ref mut node) = probe.nodes.get_mut(node_id) {
+            // Mode for node is 'push'? Otherwise, the node does not accept load reports
+            if node.mode != Mode::Push {
+                return Err(HandleLoadError::WrongMode);
+            }
+
+            // Acquire previous replica status and metrics, as they are carried over to the \
+            //   refreshed replica (this is a new replica if it never reported before)
+            let (status, mut metrics, load_queue) = match node.replicas.get(replica_id) {
+                Some(replica) => (
+                    replica.status.clone(),
+                    replica.metrics.clone(),
+                    replica
+                        .load
+                        .as_ref()
+                        .map(|load| load.queue.clone())
+                        .unwrap_or_default(),
+                ),
+                None => (
+                    Status::Healthy,
+                    ServiceStatesProbeNodeReplicaMetrics::default(),
+                    ServiceStatesProbeNodeReplicaLoadQueue::default(),
+                ),
+            };
+
+            // Assign new system metrics (loads are reported as ratios; store them as percents)
+            metrics.system = Some(ServiceStatesProbeNodeReplicaMetricsSystem {
+                cpu: (load_cpu * 100.0).round() as u16,
+                ram: (load_ram * 100.0).round() as u16,
+            });
+
+            // Bump stored replica
+            let now = SystemTime::now();
+
+            node.replicas.insert(
+                replica_id.to_string(),
+                ServiceStatesProbeNodeReplica {
+                    status,
+                    url: None,
+                    script: None,
+                    metrics,
+                    load: Some(ServiceStatesProbeNodeReplicaLoad {
+                        cpu: load_cpu,
+                        ram: load_ram,
+                        queue: load_queue,
+                    }),
+                    report: Some(ServiceStatesProbeNodeReplicaReport {
+                        time: now,
+                        interval: Duration::from_secs(interval),
+                    }),
+                    message: None,
+                    checked: Some(now),
+                },
+            );
+
+            return Ok(());
+        }
+    }
+
+    Err(HandleLoadError::NotFound)
+}
+
+pub fn handle_health(
+    probe_id: &str,
+    node_id: &str,
+    replica_id: &str,
+    interval: u64,
+    health: &Status,
+) -> Result<(), HandleHealthError> {
+    tracing::debug!(
+        "health report handle: {}:{}:{} (health: {:?})",
+        probe_id,
+        node_id,
+        replica_id,
+        health
+    );
+
+    let mut store = PROBER_STORE.write();
+
+    if let Some(ref mut probe) = store.states.probes.get_mut(probe_id) {
+        if let Some(ref mut node) = probe.nodes.get_mut(node_id) {
+            // Mode for node is 'local'? Otherwise, the node does not accept health reports
+            if node.mode != Mode::Local {
+                return Err(HandleHealthError::WrongMode);
+            }
+
+            // Bump stored replica (the reported health is used as-is for the replica status)
+            let now = SystemTime::now();
+
+            node.replicas.insert(
+                replica_id.to_string(),
+                ServiceStatesProbeNodeReplica {
+                    status: health.clone(),
+                    url: None,
+                    script: None,
+                    metrics: ServiceStatesProbeNodeReplicaMetrics::default(),
+                    load: None,
+                    report: Some(ServiceStatesProbeNodeReplicaReport {
+                        time: now,
+                        interval: Duration::from_secs(interval),
+                    }),
+                    message: None,
+                    checked: Some(now),
+                },
+            );
+
+            return Ok(());
+        }
+    }
+
+    Err(HandleHealthError::NotFound)
+}
+
+pub fn handle_flush(
+    probe_id: &str,
+    node_id: &str,
+    replica_id: &str,
+) -> Result<(), HandleFlushError> {
+    tracing::debug!(
+        "flush report handle: {}:{}:{}",
+        probe_id,
+        node_id,
+        replica_id
+    );
+
+    let mut store = PROBER_STORE.write();
+
+    if let Some(ref mut probe) = store.states.probes.get_mut(probe_id) {
+        if let Some(ref mut node) = probe.nodes.get_mut(node_id) {
+            // Only reported replicas can be flushed (configured replicas are not reported)
+            if node.mode != Mode::Push && node.mode != Mode::Local {
+                return Err(HandleFlushError::WrongMode);
+            }
+
+            // Notice: use a shift removal, as to preserve the replicas insertion order
+            if node.replicas.shift_remove(replica_id).is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    Err(HandleFlushError::NotFound)
+}
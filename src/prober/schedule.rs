@@ -0,0 +1,292 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use indexmap::IndexMap;
+
+use super::mode::Mode;
+use crate::APP_CONF;
+
+/// Scheduling options for a node; unset options fall back to the service options, then to the \
+///   global `metrics` values.
+#[derive(Clone, Default, Debug)]
+pub struct ScheduleOptions {
+    pub interval: Option<u64>,
+    pub retry: Option<u64>,
+    pub retry_delay: Option<u64>,
+    pub delay_dead: Option<u64>,
+    pub delay_sick: Option<u64>,
+}
+
+impl ScheduleOptions {
+    pub fn interval(&self, mode: &Mode) -> Duration {
+        let metrics = &APP_CONF.load().metrics;
+
+        Duration::from_secs(self.interval.unwrap_or(match mode {
+            Mode::Script => metrics.script_interval,
+            _ => metrics.poll_interval,
+        }))
+    }
+
+    pub fn retry(&self) -> u64 {
+        self.retry.unwrap_or(APP_CONF.load().metrics.poll_retry)
+    }
+
+    pub fn retry_delay(&self) -> Duration {
+        Duration::from_millis(
+            self.retry_delay
+                .unwrap_or(APP_CONF.load().metrics.poll_retry_delay),
+        )
+    }
+
+    pub fn delay_dead(&self) -> Duration {
+        Duration::from_secs(
+            self.delay_dead
+                .unwrap_or(APP_CONF.load().metrics.poll_delay_dead),
+        )
+    }
+
+    pub fn delay_sick(&self) -> Duration {
+        Duration::from_secs(
+            self.delay_sick
+                .unwrap_or(APP_CONF.load().metrics.poll_delay_sick),
+        )
+    }
+}
+
+struct ScheduleEntry {
+    interval: Duration,
+    due: Instant,
+    running: bool,
+}
+
+/// Tracks when each scheduled key (ie. replica) is due for its next check.
+pub struct Schedule<K> {
+    entries: IndexMap<K, ScheduleEntry>,
+}
+
+impl<K: Clone + Eq + Hash> Schedule<K> {
+    pub fn new() -> Self {
+        Self {
+            entries: IndexMap::new(),
+        }
+    }
+
+    /// Synchronizes scheduled keys with the current ones, along with their check interval.
+    ///
+    /// Notice: keys that were not scheduled yet get spread across their interval, so that their \
+    ///   checks do not all fire in a burst; removed keys get unscheduled.
+    pub fn synchronize(&mut self, keys: Vec<(K, Duration)>, now: Instant) {
+        let mut entries = IndexMap::with_capacity(keys.len());
+        let mut added = Vec::new();
+
+        for (key, interval) in keys {
+            match self.entries.shift_remove(&key) {
+                Some(mut entry) => {
+                    // Interval changed? Keep the current due time, unless it is past the new \
+                    //   interval (ie. the interval was shortened).
+                    if entry.interval != interval {
+                        entry.interval = interval;
+                        entry.due = entry.due.min(now + interval);
+                    }
+
+                    entries.insert(key, entry);
+                }
+                None => added.push((key, interval)),
+            }
+        }
+
+        let added_count = added.len();
+
+        for (index, (key, interval)) in added.into_iter().enumerate() {
+            entries.insert(
+                key,
+                ScheduleEntry {
+                    interval,
+                    due: now + interval.mul_f64(index as f64 / added_count as f64),
+                    running: false,
+                },
+            );
+        }
+
+        self.entries = entries;
+    }
+
+    /// Takes keys that are due, marking them as running until completed.
+    pub fn take_due(&mut self, now: Instant) -> Vec<K> {
+        let mut due: Vec<(&K, &mut ScheduleEntry)> = self
+            .entries
+            .iter_mut()
+            .filter(|(_, entry)| !entry.running && entry.due <= now)
+            .collect();
+
+        // Most overdue keys go first
+        due.sort_by_key(|(_, entry)| entry.due);
+
+        due.into_iter()
+            .map(|(key, entry)| {
+                entry.running = true;
+
+                // Notice: the next check is due one interval after this one was due (which keeps \
+                //   checks spread), unless checks are running late (then, skip missed checks).
+                entry.due += entry.interval;
+
+                if entry.due <= now {
+                    entry.due = now + entry.interval;
+                }
+
+                key.clone()
+            })
+            .collect()
+    }
+
+    /// Marks a running key as completed, so that it can be taken again once due.
+    pub fn complete(&mut self, key: &K) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.running = false;
+        }
+    }
+
+    /// Returns the earliest time a key that is not running is due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.entries
+            .values()
+            .filter(|entry| !entry.running)
+            .map(|entry| entry.due)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    fn keys(keys: &[&'static str]) -> Vec<(&'static str, Duration)> {
+        keys.iter().map(|key| (*key, INTERVAL)).collect()
+    }
+
+    #[test]
+    fn synchronize_spreads_new_keys() {
+        let now = Instant::now();
+        let mut schedule = Schedule::new();
+
+        schedule.synchronize(keys(&["a", "b", "c", "d"]), now);
+
+        assert_eq!(schedule.take_due(now), vec!["a"]);
+        assert_eq!(schedule.next_due(), Some(now + INTERVAL / 4));
+
+        assert_eq!(schedule.take_due(now + INTERVAL / 4), vec!["b"]);
+        assert_eq!(schedule.take_due(now + INTERVAL / 2), vec!["c"]);
+        assert_eq!(schedule.take_due(now + INTERVAL * 3 / 4), vec!["d"]);
+    }
+
+    #[test]
+    fn synchronize_keeps_and_removes_keys() {
+        let now = Instant::now();
+        let mut schedule = Schedule::new();
+
+        schedule.synchronize(keys(&["a", "b"]), now);
+        assert_eq!(schedule.take_due(now), vec!["a"]);
+
+        // Kept keys stay scheduled as-is, removed keys do not fire anymore
+        schedule.synchronize(keys(&["a"]), now);
+
+        assert_eq!(schedule.next_due(), None);
+
+        schedule.complete(&"a");
+
+        assert_eq!(schedule.next_due(), Some(now + INTERVAL));
+        assert_eq!(schedule.take_due(now + INTERVAL), vec!["a"]);
+
+        // Completing a removed key is a no-op
+        schedule.complete(&"b");
+
+        assert_eq!(schedule.next_due(), None);
+    }
+
+    #[test]
+    fn synchronize_shortened_interval() {
+        let now = Instant::now();
+        let mut schedule = Schedule::new();
+
+        schedule.synchronize(keys(&["a"]), now);
+        assert_eq!(schedule.take_due(now), vec!["a"]);
+        schedule.complete(&"a");
+
+        // A longer interval keeps the current due time
+        schedule.synchronize(vec![("a", INTERVAL * 2)], now);
+        assert_eq!(schedule.next_due(), Some(now + INTERVAL));
+
+        // A shorter interval does not wait past the new interval
+        schedule.synchronize(vec![("a", INTERVAL / 2)], now);
+        assert_eq!(schedule.next_due(), Some(now + INTERVAL / 2));
+    }
+
+    #[test]
+    fn take_due_never_schedules_running_keys_twice() {
+        let now = Instant::now();
+        let mut schedule = Schedule::new();
+
+        schedule.synchronize(keys(&["a"]), now);
+
+        assert_eq!(schedule.take_due(now), vec!["a"]);
+
+        // Still running, even though its next check is due
+        assert!(schedule.take_due(now + INTERVAL * 3).is_empty());
+
+        schedule.complete(&"a");
+
+        assert_eq!(schedule.take_due(now + INTERVAL * 3), vec!["a"]);
+        assert!(schedule.take_due(now + INTERVAL * 3).is_empty());
+    }
+
+    #[test]
+    fn take_due_skips_missed_runs() {
+        let now = Instant::now();
+        let mut schedule = Schedule::new();
+
+        schedule.synchronize(keys(&["a"]), now);
+
+        assert_eq!(schedule.take_due(now), vec!["a"]);
+        schedule.complete(&"a");
+
+        // Checks ran late by several intervals, thus missed checks are not caught up on
+        let late = now + INTERVAL * 3 + Duration::from_secs(5);
+
+        assert_eq!(schedule.take_due(late), vec!["a"]);
+        schedule.complete(&"a");
+
+        assert_eq!(schedule.next_due(), Some(late + INTERVAL));
+        assert!(schedule.take_due(late + INTERVAL / 2).is_empty());
+    }
+
+    #[test]
+    fn take_due_keeps_checks_spread() {
+        let now = Instant::now();
+        let mut schedule = Schedule::new();
+
+        schedule.synchronize(keys(&["a", "b"]), now);
+
+        // Both keys are overdue; the most overdue one goes first
+        let late = now + INTERVAL / 2 + Duration::from_secs(1);
+
+        assert_eq!(schedule.take_due(late), vec!["a", "b"]);
+
+        schedule.complete(&"a");
+        schedule.complete(&"b");
+
+        // Next checks are due one interval after they were due, not after they ran
+        assert_eq!(schedule.take_due(now + INTERVAL), vec!["a"]);
+        assert_eq!(schedule.next_due(), Some(now + INTERVAL * 3 / 2));
+    }
+}
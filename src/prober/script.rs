@@ -0,0 +1,348 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use indexmap::IndexMap;
+
+use super::status::Status;
+
+const SCRIPT_OUTPUT_MAXIMUM_LINES: usize = 5;
+const SCRIPT_OUTPUT_LINE_MAXIMUM_CHARS: usize = 200;
+const SCRIPT_WAIT_INTERVAL_MILLISECONDS: u64 = 50;
+
+pub fn proceed_replica_probe_script(
+    script: &str,
+    timeout: Duration,
+    environment: &IndexMap<String, String>,
+    working_directory: &Option<PathBuf>,
+) -> (Status, Option<Duration>, Option<String>) {
+    let mut command = make_command(script);
+
+    command
+        .envs(environment)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if let Some(working_directory) = working_directory {
+        command.current_dir(working_directory);
+    }
+
+    let start_time = SystemTime::now();
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            tracing::error!("prober script execution failed with error: {}", err);
+
+            return (
+                Status::Dead,
+                None,
+                Some(format!("script could not be started: {}", err)),
+            );
+        }
+    };
+
+    let (stdout, stderr) = (
+        read_output_lines(child.stdout.take()),
+        read_output_lines(child.stderr.take()),
+    );
+
+    let deadline = Instant::now() + timeout;
+
+    let (status, message) = match wait_until(&mut child, deadline) {
+        Some(code) => {
+            tracing::debug!(
+                "prober script execution succeeded with return code: {:?}",
+                code
+            );
+
+            // Return code '0' goes for 'healthy', '1' goes for 'sick'; any other code is 'dead' \
+            //   (this includes scripts killed by a signal, which have no return code)
+            let status = match code {
+                Some(0) => Status::Healthy,
+                Some(1) => Status::Sick,
+                _ => Status::Dead,
+            };
+
+            // Capture output as the failure message (prefer standard output, as this is where \
+            //   most scripts print their results)
+            let message = if status != Status::Healthy {
+                let mut lines = collect_output_lines(stdout, deadline);
+
+                lines.extend(collect_output_lines(stderr, deadline));
+                lines.truncate(SCRIPT_OUTPUT_MAXIMUM_LINES);
+
+                if lines.is_empty() {
+                    Some(match code {
+                        Some(code) => format!("script exited with code: {}", code),
+                        None => "script was terminated by a signal".to_string(),
+                    })
+                } else {
+                    Some(lines.join("\n"))
+                }
+            } else {
+                None
+            };
+
+            (status, message)
+        }
+        None => {
+            tracing::warn!(
+                "prober script execution timed out after {:?}, killed it",
+                timeout
+            );
+
+            (
+                Status::Dead,
+                Some(format!("script timed out after {}s", timeout.as_secs())),
+            )
+        }
+    };
+
+    (
+        status,
+        SystemTime::now().duration_since(start_time).ok(),
+        message,
+    )
+}
+
+fn wait_until(child: &mut Child, deadline: Instant) -> Option<Option<i32>> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(exit_status)) => return Some(exit_status.code()),
+            Ok(None) if Instant::now() < deadline => {
+                thread::sleep(Duration::from_millis(SCRIPT_WAIT_INTERVAL_MILLISECONDS));
+            }
+            Ok(None) => break,
+            Err(err) => {
+                tracing::error!("prober script could not be waited for (error: {})", err);
+
+                break;
+            }
+        }
+    }
+
+    kill(child);
+
+    let _ = child.wait();
+
+    None
+}
+
+#[cfg(unix)]
+fn make_command(script: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut command = Command::new("sh");
+
+    // Notice: the script runs in its own process group, so that any sub-process it spawns can \
+    //   be killed along with it upon timeout.
+    command.arg("-c").arg(script).process_group(0);
+
+    command
+}
+
+#[cfg(not(unix))]
+fn make_command(script: &str) -> Command {
+    let mut command = Command::new("cmd");
+
+    command.arg("/C").arg(script);
+
+    command
+}
+
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    // Kill the whole process group (the child being the group leader)
+    // Notice: this is safe, as the process group identifier is owned by the child, which was \
+    //   not reaped yet; hence, it cannot have been reused by another process group.
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    // Notice: process groups are not available on other platforms, thus only the script itself \
+    //   gets killed (not the sub-processes it spawned)
+    let _ = child.kill();
+}
+
+fn read_output_lines<R: Read + Send + 'static>(output: Option<R>) -> Receiver<Vec<String>> {
+    let (sender, receiver) = mpsc::channel();
+
+    // Notice: output is read until its end in a separate thread, otherwise the script could \
+    //   block upon writing to a full pipe; only the first lines are retained.
+    if let Some(output) = output {
+        thread::spawn(move || {
+            let lines = BufReader::new(output)
+                .split(b'\n')
+                .map_while(Result::ok)
+                .map(|line| {
+                    String::from_utf8_lossy(&line)
+                        .trim()
+                        .chars()
+                        .take(SCRIPT_OUTPUT_LINE_MAXIMUM_CHARS)
+                        .collect()
+                })
+                .filter(|line: &String| !line.is_empty())
+                .fold(Vec::new(), |mut lines, line| {
+                    if lines.len() < SCRIPT_OUTPUT_MAXIMUM_LINES {
+                        lines.push(line);
+                    }
+
+                    lines
+                });
+
+            let _ = sender.send(lines);
+        });
+    }
+
+    receiver
+}
+
+fn collect_output_lines(receiver: Receiver<Vec<String>>, deadline: Instant) -> Vec<String> {
+    // Notice: sub-processes left running in the background might hold the output open, in which \
+    //   case no output is collected past the deadline.
+    receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .unwrap_or_default()
+}
+
+// Notice: tests run scripts through 'sh', which is only expected on Unix platforms
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn run(script: &str) -> (Status, Option<String>) {
+        let (status, _, message) =
+            proceed_replica_probe_script(script, TIMEOUT, &IndexMap::new(), &None);
+
+        (status, message)
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(run("exit 0"), (Status::Healthy, None));
+        assert_eq!(
+            run("exit 1"),
+            (Status::Sick, Some("script exited with code: 1".to_string()))
+        );
+        assert_eq!(
+            run("exit 3"),
+            (Status::Dead, Some("script exited with code: 3".to_string()))
+        );
+        assert_eq!(
+            run("kill -9 $$"),
+            (
+                Status::Dead,
+                Some("script was terminated by a signal".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn output_is_captured_as_message() {
+        // Standard output goes first, and blank lines are skipped
+        assert_eq!(
+            run("echo 'disk full' >&2; echo; echo '  /var at 99%  '; exit 2"),
+            (Status::Dead, Some("/var at 99%\ndisk full".to_string()))
+        );
+
+        // Output is not reported for healthy scripts
+        assert_eq!(run("echo fine"), (Status::Healthy, None));
+
+        // Only the first lines are retained, and long lines are cut
+        let (_, message) =
+            run("for i in 1 2 3 4 5 6 7; do echo line $i; done; printf '%0300d' 0 >&2; exit 1");
+
+        assert_eq!(
+            message.as_deref(),
+            Some("line 1\nline 2\nline 3\nline 4\nline 5")
+        );
+
+        let (_, message) = run("printf '%0300d' 0; exit 1");
+
+        assert_eq!(
+            message.map(|message| message.len()),
+            Some(SCRIPT_OUTPUT_LINE_MAXIMUM_CHARS)
+        );
+    }
+
+    #[test]
+    fn environment_and_working_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        let environment = [("PROBE_TARGET".to_string(), "db-1".to_string())].into();
+
+        let (status, _, message) = proceed_replica_probe_script(
+            "echo \"$PROBE_TARGET in $(basename \"$(pwd)\")\"; exit 1",
+            TIMEOUT,
+            &environment,
+            &Some(directory.path().to_path_buf()),
+        );
+
+        assert_eq!(status, Status::Sick);
+        assert_eq!(
+            message,
+            Some(format!(
+                "db-1 in {}",
+                directory.path().file_name().unwrap().to_string_lossy()
+            ))
+        );
+    }
+
+    #[test]
+    fn timeout_kills_process_group() {
+        let directory = tempfile::tempdir().unwrap();
+        let marker_path = directory.path().join("marker");
+
+        let start = Instant::now();
+
+        // The script leaves a sub-process running, which holds the output open
+        let (status, _, message) = proceed_replica_probe_script(
+            &format!("(sleep 2; touch {}) & wait", marker_path.display()),
+            Duration::from_secs(1),
+            &IndexMap::new(),
+            &None,
+        );
+
+        assert_eq!(status, Status::Dead);
+        assert_eq!(message.as_deref(), Some("script timed out after 1s"));
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        // Sub-process was killed along with the script, thus it never creates the marker
+        thread::sleep(Duration::from_secs(2));
+
+        assert!(!marker_path.exists());
+    }
+
+    #[test]
+    fn missing_working_directory() {
+        let (status, _, message) = proceed_replica_probe_script(
+            "exit 0",
+            TIMEOUT,
+            &IndexMap::new(),
+            &Some(PathBuf::from("/nonexistent/directory")),
+        );
+
+        assert_eq!(status, Status::Dead);
+        assert!(message
+            .unwrap()
+            .starts_with("script could not be started: "));
+    }
+}
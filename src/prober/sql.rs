@@ -0,0 +1,323 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+
+use mysql_async::prelude::Queryable;
+use tokio::time::timeout as with_timeout;
+use tokio_postgres::{NoTls, SimpleQueryMessage};
+
+use super::replica::ReplicaDatabase;
+use super::status::Status;
+
+const SQL_QUERY_DEFAULT: &str = "SELECT 1";
+
+/// SQL options for a node; the query defaults to `SELECT 1` if not set.
+#[derive(Clone, Default, Debug)]
+pub struct SqlOptions {
+    pub query: Option<String>,
+    pub expected_value: Option<String>,
+    pub value_sick_above: Option<f64>,
+    pub value_dead_above: Option<f64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Postgres(#[from] tokio_postgres::Error),
+
+    #[error(transparent)]
+    Mysql(#[from] mysql_async::Error),
+
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+}
+
+pub async fn proceed_replica_probe_poll_sql(
+    database: &ReplicaDatabase,
+    url: &str,
+    timeout: Duration,
+    options: &SqlOptions,
+) -> (Status, Option<Duration>, Option<String>) {
+    let query = options.query.as_deref().unwrap_or(SQL_QUERY_DEFAULT);
+
+    tracing::debug!(
+        "prober poll will fire for {} target with query: '{}'",
+        database.name(),
+        query
+    );
+
+    // Acquire query start time (used for latency calculation, which includes connecting)
+    let query_start_time = SystemTime::now();
+
+    let value = match with_timeout(timeout, query_value(database, url, query)).await {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            tracing::debug!(
+                "prober poll error for {} target (error: {})",
+                database.name(),
+                err
+            );
+
+            return (
+                Status::Dead,
+                None,
+                Some(format!("{}: {}", database.name(), err)),
+            );
+        }
+        Err(_) => {
+            tracing::debug!("prober poll timed out for {} target", database.name());
+
+            return (
+                Status::Dead,
+                None,
+                Some(format!(
+                    "{}: timed out after {}s",
+                    database.name(),
+                    timeout.as_secs()
+                )),
+            );
+        }
+    };
+
+    let query_latency = SystemTime::now().duration_since(query_start_time).ok();
+
+    tracing::debug!(
+        "prober poll result received for {} target with value: {:?}",
+        database.name(),
+        value
+    );
+
+    match classify(value, options) {
+        (Status::Dead, message) => (
+            Status::Dead,
+            None,
+            message.map(|message| format!("{}: {}", database.name(), message)),
+        ),
+        (status, message) => (
+            status,
+            query_latency,
+            message.map(|message| format!("{}: {}", database.name(), message)),
+        ),
+    }
+}
+
+async fn query_value(
+    database: &ReplicaDatabase,
+    url: &str,
+    query: &str,
+) -> Result<Option<String>, Error> {
+    // Notice: queries are sent using the simple (ie. text) protocol of each database, so that \
+    //   the returned value can be compared as text whatever its type.
+    match database {
+        ReplicaDatabase::Postgres => {
+            let config = tokio_postgres::Config::from_str(url)
+                .map_err(|err| Error::InvalidUrl(err.to_string()))?;
+
+            // Notice: TLS is not supported (URLs requiring it are rejected by 'check-config'), \
+            //   though servers that merely prefer it still accept plain connections.
+            let (client, connection) = config.connect(NoTls).await?;
+
+            // Notice: the connection performs the actual communication with the database, thus \
+            //   it must be polled aside; it ends once the client is dropped.
+            tokio::spawn(async move {
+                if let Err(err) = connection.await {
+                    tracing::debug!("prober poll postgres connection closed (error: {})", err);
+                }
+            });
+
+            let messages = client.simple_query(query).await?;
+
+            // Acquire first column of first returned row
+            for message in messages {
+                if let SimpleQueryMessage::Row(row) = message {
+                    return Ok(row.try_get(0)?.map(str::to_string));
+                }
+            }
+
+            Ok(None)
+        }
+        ReplicaDatabase::Mysql => {
+            let opts = mysql_async::Opts::from_url(url)
+                .map_err(|err| Error::InvalidUrl(err.to_string()))?;
+
+            let mut connection = mysql_async::Conn::new(opts).await?;
+
+            let row: Result<Option<mysql_async::Row>, _> = connection.query_first(query).await;
+
+            // Notice: the connection is closed on a best-effort basis (even if the query failed), \
+            //   instead of leaving an aborted connection server-side.
+            let _ = connection.disconnect().await;
+
+            Ok(row?.and_then(|mut row| row.take_opt::<Option<String>, _>(0)?.ok()?))
+        }
+    }
+}
+
+fn classify(value: Option<String>, options: &SqlOptions) -> (Status, Option<String>) {
+    // No value returned (ie. no row, or a NULL value)? Consider as DOWN.
+    let value = match value {
+        Some(value) => value,
+        None => return (Status::Dead, Some("query returned no value".to_string())),
+    };
+
+    // Check returned value against expected value? (if configured)
+    if let Some(ref expected_value) = options.expected_value {
+        if &value != expected_value {
+            return (
+                Status::Dead,
+                Some(format!(
+                    "unexpected value: {} (expected: {})",
+                    value, expected_value
+                )),
+            );
+        }
+    }
+
+    // Check returned value against thresholds? (if configured, eg. for a replication lag)
+    if options.value_sick_above.is_some() || options.value_dead_above.is_some() {
+        let number = match value.trim().parse::<f64>() {
+            Ok(number) => number,
+            Err(_) => {
+                return (
+                    Status::Dead,
+                    Some(format!("value is not a number: {}", value)),
+                )
+            }
+        };
+
+        if let Some(value_dead_above) = options.value_dead_above {
+            if number > value_dead_above {
+                return (
+                    Status::Dead,
+                    Some(format!(
+                        "value {} is above dead threshold ({})",
+                        value, value_dead_above
+                    )),
+                );
+            }
+        }
+
+        if let Some(value_sick_above) = options.value_sick_above {
+            if number > value_sick_above {
+                return (
+                    Status::Sick,
+                    Some(format!(
+                        "value {} is above sick threshold ({})",
+                        value, value_sick_above
+                    )),
+                );
+            }
+        }
+    }
+
+    (Status::Healthy, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(
+        expected_value: Option<&str>,
+        value_sick_above: Option<f64>,
+        value_dead_above: Option<f64>,
+    ) -> SqlOptions {
+        SqlOptions {
+            query: None,
+            expected_value: expected_value.map(str::to_string),
+            value_sick_above,
+            value_dead_above,
+        }
+    }
+
+    fn status(value: Option<&str>, options: &SqlOptions) -> Status {
+        classify(value.map(str::to_string), options).0
+    }
+
+    #[test]
+    fn no_value_is_dead() {
+        let (status, message) = classify(None, &options(None, None, None));
+
+        assert_eq!(status, Status::Dead);
+        assert_eq!(message.as_deref(), Some("query returned no value"));
+    }
+
+    #[test]
+    fn any_value_is_healthy_without_checks() {
+        assert_eq!(
+            classify(Some("anything".to_string()), &options(None, None, None)),
+            (Status::Healthy, None)
+        );
+    }
+
+    #[test]
+    fn expected_value() {
+        let options = options(Some("ok"), None, None);
+
+        assert_eq!(status(Some("ok"), &options), Status::Healthy);
+
+        let (status, message) = classify(Some("ko".to_string()), &options);
+
+        assert_eq!(status, Status::Dead);
+        assert_eq!(
+            message.as_deref(),
+            Some("unexpected value: ko (expected: ok)")
+        );
+    }
+
+    #[test]
+    fn non_numeric_value_with_thresholds() {
+        let options = options(None, Some(10.0), None);
+
+        assert_eq!(
+            classify(Some("lagging".to_string()), &options),
+            (
+                Status::Dead,
+                Some("value is not a number: lagging".to_string())
+            )
+        );
+
+        // Surrounding whitespace is ignored
+        assert_eq!(status(Some(" 5 \n"), &options), Status::Healthy);
+    }
+
+    #[test]
+    fn threshold_boundaries() {
+        let options = options(None, Some(10.0), Some(60.0));
+
+        // Thresholds are exclusive (values must be above them)
+        assert_eq!(status(Some("10"), &options), Status::Healthy);
+        assert_eq!(status(Some("10.5"), &options), Status::Sick);
+        assert_eq!(status(Some("60"), &options), Status::Sick);
+        assert_eq!(status(Some("60.001"), &options), Status::Dead);
+        assert_eq!(status(Some("-1"), &options), Status::Healthy);
+    }
+
+    #[test]
+    fn single_threshold() {
+        let sick_only = options(None, Some(10.0), None);
+
+        assert_eq!(status(Some("1000"), &sick_only), Status::Sick);
+
+        let dead_only = options(None, None, Some(10.0));
+
+        assert_eq!(status(Some("10"), &dead_only), Status::Healthy);
+        assert_eq!(status(Some("11"), &dead_only), Status::Dead);
+    }
+
+    #[test]
+    fn expected_value_is_checked_before_thresholds() {
+        let options = options(Some("5"), Some(1.0), None);
+
+        assert_eq!(status(Some("5"), &options), Status::Sick);
+        assert_eq!(status(Some("0"), &options), Status::Dead);
+    }
+}
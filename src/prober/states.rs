@@ -15,16 +15,23 @@
 // Copyright: 2018, Valerian Saliou <valerian@valeriansaliou.name>
 // License: Mozilla Public License v2.0 (MPL v2.0)
 
+use std::path::PathBuf;
 use std::time::{Duration, SystemTime};
 
 use indexmap::IndexMap;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+use super::http::HttpClientOptions;
+use super::icmp::IcmpOptions;
 use super::mode::Mode;
 use super::replica::ReplicaUrl;
+use super::schedule::ScheduleOptions;
+use super::sql::SqlOptions;
 use super::status::Status;
-use crate::config::probe::HttpMethod;
+use super::udp::UdpOptions;
+use super::websocket::WebSocketOptions;
+use crate::config::probe::{HttpBodyJsonAssertion, HttpMethod};
 
 #[derive(Serialize)]
 pub struct ServiceStates {
@@ -42,19 +49,14 @@ pub struct ServiceStatesProbe {
     pub nodes: IndexMap<String, ServiceStatesProbeNode>,
 }
 
-#[derive(Clone, Copy, Deserialize, Serialize)]
+#[derive(Clone, Copy, Deserialize, Serialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum SocketType {
+    #[default]
     Raw,
     Dgram,
 }
 
-impl Default for SocketType {
-    fn default() -> Self {
-        Self::Raw
-    }
-}
-
 impl From<SocketType> for socket2::Type {
     fn from(ty: SocketType) -> Self {
         match ty {
@@ -70,16 +72,44 @@ pub struct ServiceStatesProbeNode {
     pub label: String,
     pub mode: Mode,
     pub replicas: IndexMap<String, ServiceStatesProbeNodeReplica>,
+    #[serde(skip)]
+    pub schedule: ScheduleOptions,
     #[serde(default)]
     pub http_cache_buster: bool,
     #[serde(default)]
     #[serde(with = "http_serde::header_map")]
     pub http_headers: http::HeaderMap,
+    #[serde(skip)]
+    pub http_client: HttpClientOptions,
     pub http_method: Option<HttpMethod>,
     pub http_body: Option<String>,
+    pub http_status_healthy_above: Option<u16>,
+    pub http_status_healthy_below: Option<u16>,
+    #[serde(with = "crate::util::regex_map")]
+    pub http_headers_healthy_match: IndexMap<String, Regex>,
     #[serde(default)]
     #[serde(with = "serde_regex")]
     pub http_body_healthy_match: Option<Regex>,
+    #[serde(default)]
+    #[serde(with = "serde_regex")]
+    pub http_body_unhealthy_match: Option<Regex>,
+    pub http_body_json_assertions: Vec<HttpBodyJsonAssertion>,
+    pub tcp_send: Option<String>,
+    #[serde(default)]
+    #[serde(with = "serde_regex")]
+    pub tcp_response_healthy_match: Option<Regex>,
+    #[serde(skip)]
+    pub udp: UdpOptions,
+    #[serde(skip)]
+    pub websocket: WebSocketOptions,
+    pub dns_expected_value: Option<String>,
+    #[serde(skip)]
+    pub icmp: IcmpOptions,
+    #[serde(skip)]
+    pub sql: SqlOptions,
+    pub script_timeout: Option<u64>,
+    pub script_environment: IndexMap<String, String>,
+    pub script_working_directory: Option<PathBuf>,
     pub rabbitmq: Option<ServiceStatesProbeNodeRabbitMQ>,
 }
 
@@ -91,6 +121,8 @@ pub struct ServiceStatesProbeNodeReplica {
     pub metrics: ServiceStatesProbeNodeReplicaMetrics,
     pub load: Option<ServiceStatesProbeNodeReplicaLoad>,
     pub report: Option<ServiceStatesProbeNodeReplicaReport>,
+    pub message: Option<String>,
+    pub checked: Option<SystemTime>,
 }
 
 #[derive(Serialize, Clone)]
@@ -100,46 +132,71 @@ pub struct ServiceStatesProbeNodeRabbitMQ {
     pub queue_nack_dead_above: Option<u32>,
 }
 
-#[derive(Serialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ServiceStatesProbeNodeReplicaMetrics {
     pub latency: Option<u64>,
     pub system: Option<ServiceStatesProbeNodeReplicaMetricsSystem>,
     pub rabbitmq: Option<ServiceStatesProbeNodeReplicaMetricsRabbitMQ>,
+    pub tls: Option<ServiceStatesProbeNodeReplicaMetricsTls>,
+    pub icmp: Option<ServiceStatesProbeNodeReplicaMetricsIcmp>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ServiceStatesProbeNodeReplicaMetricsSystem {
     pub cpu: u16,
     pub ram: u16,
 }
 
-#[derive(Serialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ServiceStatesProbeNodeReplicaMetricsRabbitMQ {
     pub queue_ready: u32,
     pub queue_nack: u32,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServiceStatesProbeNodeReplicaMetricsTls {
+    pub expiry_days: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServiceStatesProbeNodeReplicaMetricsIcmp {
+    pub sent: u16,
+    pub received: u16,
+    pub loss: u8,
+    pub rtt_minimum: Option<f64>,
+    pub rtt_average: Option<f64>,
+    pub rtt_maximum: Option<f64>,
+    pub jitter: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ServiceStatesProbeNodeReplicaLoad {
     pub cpu: f32,
     pub ram: f32,
     pub queue: ServiceStatesProbeNodeReplicaLoadQueue,
 }
 
-#[derive(Serialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct ServiceStatesProbeNodeReplicaLoadQueue {
     pub loaded: bool,
     pub stalled: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ServiceStatesProbeNodeReplicaReport {
     pub time: SystemTime,
     pub interval: Duration,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct ServiceStatesNotifier {
     pub reminder_backoff_counter: u16,
     pub reminder_ignore_until: Option<SystemTime>,
+    pub acknowledgement: Option<ServiceStatesNotifierAcknowledgement>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ServiceStatesNotifierAcknowledgement {
+    pub by: String,
+    pub time: SystemTime,
 }
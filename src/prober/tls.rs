@@ -0,0 +1,298 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout as with_timeout;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+static TLS_CLIENT_CONFIGS: Lazy<RwLock<HashMap<TlsOptions, Arc<ClientConfig>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// TLS options for a node, shared by the certificate check and the HTTP client.
+#[derive(Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub struct TlsOptions {
+    pub insecure: bool,
+    pub ca_path: Option<PathBuf>,
+    pub client_certificate_path: Option<PathBuf>,
+    pub client_key_path: Option<PathBuf>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid server name: {0}")]
+    InvalidName(String),
+
+    #[error("could not resolve any address")]
+    NoAddress,
+
+    #[error("connection failed: {0}")]
+    Connect(#[source] io::Error),
+
+    #[error("handshake failed: {0}")]
+    Handshake(#[source] io::Error),
+
+    #[error("no certificate was presented")]
+    NoCertificate,
+
+    #[error("certificate could not be parsed")]
+    InvalidCertificate,
+
+    #[error("certificate is expired")]
+    Expired,
+
+    #[error("could not read {0:?}: {1}")]
+    Read(PathBuf, #[source] io::Error),
+
+    #[error("no usable certificate or key in {0:?}")]
+    InvalidPem(PathBuf),
+
+    #[error(transparent)]
+    Tls(#[from] rustls::Error),
+}
+
+pub struct Certificate {
+    pub not_after: SystemTime,
+    pub handshake: Duration,
+}
+
+impl Certificate {
+    /// Returns the number of full days until the certificate expires.
+    pub fn expiry_days(&self) -> u32 {
+        self.not_after
+            .duration_since(SystemTime::now())
+            .map(|remaining| (remaining.as_secs() / 86400) as u32)
+            .unwrap_or(0)
+    }
+}
+
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Performs a TLS handshake with the target, and returns its validated leaf certificate.
+///
+/// Notice: the handshake fails if the certificate chain does not lead to a trusted root (eg. \
+///   self-signed certificates), if the certificate is expired, or if it is not valid for the \
+///   host name; any of those is reported as an error. Insecure options skip the chain and host \
+///   name checks, though expired certificates are still reported as errors.
+pub async fn fetch_certificate(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    options: &TlsOptions,
+) -> Result<Certificate, Error> {
+    let address = lookup_host((host, port))
+        .await
+        .map_err(Error::Connect)?
+        .next()
+        .ok_or(Error::NoAddress)?;
+
+    let stream = with_timeout(timeout, TcpStream::connect(address))
+        .await
+        .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+        .map_err(Error::Connect)?;
+
+    // Acquire handshake start time (used for latency calculation)
+    let handshake_start_time = SystemTime::now();
+
+    let mut stream = handshake(stream, host, timeout, options, &[]).await?;
+
+    let handshake = SystemTime::now()
+        .duration_since(handshake_start_time)
+        .unwrap_or_default();
+
+    let not_after = {
+        // Notice: the leaf certificate always comes first in the presented chain
+        let leaf = stream
+            .get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .ok_or(Error::NoCertificate)?;
+
+        let (_, certificate) =
+            X509Certificate::from_der(&leaf.0).map_err(|_| Error::InvalidCertificate)?;
+
+        u64::try_from(certificate.validity().not_after.timestamp())
+            .map(|timestamp| UNIX_EPOCH + Duration::from_secs(timestamp))
+            .map_err(|_| Error::InvalidCertificate)?
+    };
+
+    // Notice: this can only be reached with insecure options, as the handshake fails otherwise
+    if not_after <= SystemTime::now() {
+        return Err(Error::Expired);
+    }
+
+    // Notice: the close notification is sent on a best-effort basis, as it is not required
+    let _ = with_timeout(timeout, stream.shutdown()).await;
+
+    Ok(Certificate {
+        not_after,
+        handshake,
+    })
+}
+
+/// Performs a TLS handshake over a connected stream, advertising given ALPN protocols (if any).
+pub async fn handshake(
+    stream: TcpStream,
+    host: &str,
+    timeout: Duration,
+    options: &TlsOptions,
+    alpn_protocols: &[&[u8]],
+) -> Result<TlsStream<TcpStream>, Error> {
+    let server_name =
+        ServerName::try_from(host).map_err(|_| Error::InvalidName(host.to_string()))?;
+
+    let mut client_config = acquire_client_config(options)?;
+
+    // Notice: cached configurations are shared, thus ALPN protocols are set on a copy
+    if !alpn_protocols.is_empty() {
+        let mut client_config_alpn = (*client_config).clone();
+
+        client_config_alpn.alpn_protocols = alpn_protocols
+            .iter()
+            .map(|protocol| protocol.to_vec())
+            .collect();
+
+        client_config = Arc::new(client_config_alpn);
+    }
+
+    with_timeout(
+        timeout,
+        TlsConnector::from(client_config).connect(server_name, stream),
+    )
+    .await
+    .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+    .map_err(Error::Handshake)
+}
+
+/// Reads all certificates from a PEM file (eg. a CA bundle, or a client certificate chain).
+pub fn read_certificates(path: &Path) -> Result<Vec<Vec<u8>>, Error> {
+    let file = File::open(path).map_err(|err| Error::Read(path.to_path_buf(), err))?;
+
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(file))
+        .map_err(|err| Error::Read(path.to_path_buf(), err))?;
+
+    if certificates.is_empty() {
+        return Err(Error::InvalidPem(path.to_path_buf()));
+    }
+
+    Ok(certificates)
+}
+
+fn read_private_key(path: &Path) -> Result<Vec<u8>, Error> {
+    let file = File::open(path).map_err(|err| Error::Read(path.to_path_buf(), err))?;
+    let mut reader = BufReader::new(file);
+
+    // Use the first private key found in the file (whatever its format)
+    while let Some(item) =
+        rustls_pemfile::read_one(&mut reader).map_err(|err| Error::Read(path.to_path_buf(), err))?
+    {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(key),
+            _ => {}
+        }
+    }
+
+    Err(Error::InvalidPem(path.to_path_buf()))
+}
+
+/// Clears cached client configurations, so that certificate files get read again.
+pub fn clear_client_configs() {
+    TLS_CLIENT_CONFIGS.write().clear();
+}
+
+fn acquire_client_config(options: &TlsOptions) -> Result<Arc<ClientConfig>, Error> {
+    if let Some(client_config) = TLS_CLIENT_CONFIGS.read().get(options) {
+        return Ok(client_config.clone());
+    }
+
+    let client_config = Arc::new(make_client_config(options)?);
+
+    TLS_CLIENT_CONFIGS
+        .write()
+        .insert(options.clone(), client_config.clone());
+
+    Ok(client_config)
+}
+
+fn make_client_config(options: &TlsOptions) -> Result<ClientConfig, Error> {
+    let mut root_store = RootCertStore::empty();
+
+    // Trust the custom CA bundle if any (in place of the well-known roots)
+    if let Some(ref ca_path) = options.ca_path {
+        let (added, _) = root_store.add_parsable_certificates(&read_certificates(ca_path)?);
+
+        if added == 0 {
+            return Err(Error::InvalidPem(ca_path.clone()));
+        }
+    } else {
+        root_store.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(
+            |anchor| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    anchor.subject,
+                    anchor.spki,
+                    anchor.name_constraints,
+                )
+            },
+        ));
+    }
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store);
+
+    // Present a client certificate? (for mutual TLS)
+    let mut client_config = match (&options.client_certificate_path, &options.client_key_path) {
+        (Some(certificate_path), Some(key_path)) => builder.with_single_cert(
+            read_certificates(certificate_path)?
+                .into_iter()
+                .map(rustls::Certificate)
+                .collect(),
+            PrivateKey(read_private_key(key_path)?),
+        )?,
+        _ => builder.with_no_client_auth(),
+    };
+
+    if options.insecure {
+        client_config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoVerification));
+    }
+
+    Ok(client_config)
+}
@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use tokio::net::lookup_host;
+use tokio::task;
+
+use super::status::Status;
+
+const UDP_DATAGRAM_MAXIMUM_BYTES: usize = 65536;
+
+/// UDP options for a node; the payload is taken from either `udp_send` or `udp_send_hex`.
+#[derive(Clone, Default, Debug)]
+pub struct UdpOptions {
+    pub payload: Vec<u8>,
+    pub response_match: Option<Regex>,
+}
+
+pub async fn proceed_replica_probe_poll_udp(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    options: &UdpOptions,
+) -> (Status, Option<Duration>) {
+    let address_values: Vec<SocketAddr> = match lookup_host((host, port)).await {
+        Ok(address) => address.collect(),
+        Err(err) => {
+            tracing::error!(
+                "prober poll address for udp replica is invalid: {}:{} (error: {})",
+                host,
+                port,
+                err
+            );
+
+            // Consider invalid URL as a failure
+            return (Status::Dead, None);
+        }
+    };
+
+    if address_values.is_empty() {
+        tracing::debug!(
+            "prober poll did not resolve any address for udp replica: {}:{}",
+            host,
+            port
+        );
+
+        // Consider empty as a failure
+        return (Status::Dead, None);
+    }
+
+    // Notice: as for TCP and ICMP probes, all resolved addresses are checked, as a single \
+    //   unresponsive address is sufficient for the whole replica to be considered down.
+    tracing::debug!(
+        "prober poll will fire for udp host: {}:{} ({} targets)",
+        host,
+        port,
+        address_values.len()
+    );
+
+    // Storage variable for the maximum latency found for probed addresses
+    let mut maximum_latency = None;
+
+    // Probe all returned addresses (sequentially)
+    for address_value in &address_values {
+        let (address, options) = (*address_value, options.clone());
+
+        let result = task::spawn_blocking(move || {
+            proceed_replica_probe_poll_udp_address(&address, timeout, &options)
+        })
+        .await
+        .unwrap_or_else(|err| Err(io::Error::other(err)));
+
+        match result {
+            Ok(latency) => {
+                tracing::debug!("prober poll success for udp target: {}", address_value);
+
+                maximum_latency = maximum_latency.max(Some(latency));
+            }
+            Err(err) => {
+                tracing::debug!(
+                    "prober poll error for udp target: {} (error: {})",
+                    address_value,
+                    err
+                );
+
+                return (Status::Dead, None);
+            }
+        }
+    }
+
+    (Status::Healthy, maximum_latency)
+}
+
+fn proceed_replica_probe_poll_udp_address(
+    address: &SocketAddr,
+    timeout: Duration,
+    options: &UdpOptions,
+) -> Result<Duration, io::Error> {
+    // Notice: the whole exchange must complete before the dead delay, whatever the number of \
+    //   unmatched datagrams received in the meantime.
+    let deadline = Instant::now() + timeout;
+
+    let local_address: SocketAddr = if address.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+
+    let socket = UdpSocket::bind(local_address)?;
+
+    // Notice: connecting the socket restricts received datagrams to those sent by the target, \
+    //   and reports ICMP 'port unreachable' errors (ie. closed ports) on receive. A blocking \
+    //   socket is used, as socket errors do not wake asynchronous receives up.
+    socket.connect(address)?;
+    socket.set_write_timeout(Some(timeout))?;
+
+    // Acquire send start time (used for latency calculation)
+    let send_start_time = Instant::now();
+
+    socket.send(&options.payload)?;
+
+    let mut buffer = vec![0; UDP_DATAGRAM_MAXIMUM_BYTES];
+
+    // Receive datagrams until one matches (other datagrams might be received, eg. replies to \
+    //   previous requests that arrived late)
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        socket.set_read_timeout(Some(remaining))?;
+
+        let received = match socket.recv(&mut buffer) {
+            Ok(received) => received,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            Err(err) => return Err(err),
+        };
+
+        let response = String::from_utf8_lossy(&buffer[..received]);
+
+        tracing::debug!(
+            "checking prober poll response for udp target: {} for any match: {}",
+            address,
+            response
+        );
+
+        match options.response_match {
+            Some(ref response_match) if !response_match.is_match(&response) => continue,
+            _ => return Ok(send_start_time.elapsed()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use regex::Regex;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// Replies to the first received datagram with each given datagram, in order.
+    fn serve(replies: Vec<&'static [u8]>) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let (size, peer) = socket.recv_from(&mut buffer).unwrap();
+
+            assert_eq!(&buffer[..size], b"\xff\xff\xff\xffgetstatus");
+
+            for reply in replies {
+                socket.send_to(reply, peer).unwrap();
+            }
+        });
+
+        address
+    }
+
+    fn options(response_match: Option<&str>) -> UdpOptions {
+        UdpOptions {
+            payload: b"\xff\xff\xff\xffgetstatus".to_vec(),
+            response_match: response_match.map(|pattern| Regex::new(pattern).unwrap()),
+        }
+    }
+
+    #[test]
+    fn any_response_without_match() {
+        let address = serve(vec![b"anything"]);
+
+        assert!(proceed_replica_probe_poll_udp_address(&address, TIMEOUT, &options(None)).is_ok());
+    }
+
+    #[test]
+    fn unmatched_responses_are_skipped() {
+        let address = serve(vec![b"late reply", b"\xff\xff\xff\xffstatusResponse"]);
+
+        assert!(proceed_replica_probe_poll_udp_address(
+            &address,
+            TIMEOUT,
+            &options(Some("statusResponse"))
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn unmatched_responses_time_out() {
+        let address = serve(vec![b"late reply", b"another late reply"]);
+
+        let err = proceed_replica_probe_poll_udp_address(
+            &address,
+            TIMEOUT,
+            &options(Some("statusResponse")),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use futures_util::{SinkExt, StreamExt};
+use http::header::HeaderMap;
+use regex::Regex;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::{timeout as with_timeout, timeout_at, Instant};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::WebSocketStream;
+use url::{Host, Url};
+
+use super::http::make_default_headers;
+use super::status::Status;
+use super::tls::{self, TlsOptions};
+
+const WEBSOCKET_ALPN_PROTOCOL: &[u8] = b"http/1.1";
+
+/// WebSocket options for a node; without a response match, the replica is healthy once upgraded.
+#[derive(Clone, Default, Debug)]
+pub struct WebSocketOptions {
+    pub send: Option<String>,
+    pub response_match: Option<Regex>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid url")]
+    InvalidUrl,
+
+    #[error("could not resolve any address")]
+    NoAddress,
+
+    #[error("connection failed: {0}")]
+    Connect(#[source] io::Error),
+
+    #[error(transparent)]
+    Tls(#[from] tls::Error),
+
+    #[error(transparent)]
+    WebSocket(#[from] tungstenite::Error),
+
+    #[error("connection closed by server")]
+    Closed,
+
+    #[error("timed out after {0}s")]
+    TimedOut(u64),
+}
+
+pub async fn proceed_replica_probe_poll_websocket(
+    url: &str,
+    timeout: Duration,
+    headers: &HeaderMap,
+    tls_options: &TlsOptions,
+    options: &WebSocketOptions,
+) -> (Status, Option<Duration>, Option<String>) {
+    tracing::debug!("prober poll will fire for websocket target: {}", url);
+
+    // Acquire exchange start time (used for latency calculation, which includes connecting)
+    let exchange_start_time = SystemTime::now();
+
+    match exchange(url, timeout, headers, tls_options, options).await {
+        Ok(()) => (
+            Status::Healthy,
+            SystemTime::now().duration_since(exchange_start_time).ok(),
+            None,
+        ),
+        Err(err) => {
+            tracing::debug!(
+                "prober poll error for websocket target: {} (error: {})",
+                url,
+                err
+            );
+
+            (Status::Dead, None, Some(format!("websocket: {}", err)))
+        }
+    }
+}
+
+async fn exchange(
+    url: &str,
+    timeout: Duration,
+    headers: &HeaderMap,
+    tls_options: &TlsOptions,
+    options: &WebSocketOptions,
+) -> Result<(), Error> {
+    // Notice: the whole exchange must complete before the dead delay, though the connection \
+    //   still gets closed if messages time out (which is why this is not a single timeout).
+    let deadline = Instant::now() + timeout;
+
+    let url = Url::parse(url).map_err(|_| Error::InvalidUrl)?;
+    let port = url.port_or_known_default().ok_or(Error::InvalidUrl)?;
+
+    // Notice: IPv6 addresses are formatted without brackets, as expected by the resolver
+    let host = match url.host().ok_or(Error::InvalidUrl)? {
+        Host::Domain(domain_value) => domain_value.to_string(),
+        Host::Ipv4(ipv4_value) => ipv4_value.to_string(),
+        Host::Ipv6(ipv6_value) => ipv6_value.to_string(),
+    };
+
+    let stream = timeout_at(deadline, async {
+        let address = lookup_host((host.as_str(), port))
+            .await
+            .map_err(Error::Connect)?
+            .next()
+            .ok_or(Error::NoAddress)?;
+
+        TcpStream::connect(address).await.map_err(Error::Connect)
+    })
+    .await
+    .map_err(|_| Error::TimedOut(timeout.as_secs()))??;
+
+    if url.scheme() == "wss" {
+        let stream = timeout_at(
+            deadline,
+            tls::handshake(
+                stream,
+                &host,
+                timeout,
+                tls_options,
+                &[WEBSOCKET_ALPN_PROTOCOL],
+            ),
+        )
+        .await
+        .map_err(|_| Error::TimedOut(timeout.as_secs()))??;
+
+        upgrade(stream, &url, deadline, timeout, headers, options).await
+    } else {
+        upgrade(stream, &url, deadline, timeout, headers, options).await
+    }
+}
+
+async fn upgrade<S>(
+    stream: S,
+    url: &Url,
+    deadline: Instant,
+    timeout: Duration,
+    headers: &HeaderMap,
+    options: &WebSocketOptions,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut request = url.as_str().into_client_request()?;
+
+    request.headers_mut().extend(make_default_headers());
+    request.headers_mut().extend(headers.clone());
+
+    // Notice: the upgrade handshake fails if the server does not answer with a '101' status, \
+    //   or if its 'Sec-WebSocket-Accept' header does not match the request key.
+    let (websocket, _) = timeout_at(deadline, tokio_tungstenite::client_async(request, stream))
+        .await
+        .map_err(|_| Error::TimedOut(timeout.as_secs()))??;
+
+    check_and_close(websocket, deadline, timeout, options).await
+}
+
+async fn check_and_close<S>(
+    mut websocket: WebSocketStream<S>,
+    deadline: Instant,
+    timeout: Duration,
+    options: &WebSocketOptions,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let result = timeout_at(deadline, check_messages(&mut websocket, options))
+        .await
+        .unwrap_or(Err(Error::TimedOut(timeout.as_secs())));
+
+    // Notice: the connection is closed on a best-effort basis (even if the check failed), by \
+    //   sending a close frame, then waiting for the server to close as well (in the background, \
+    //   so that it does not count in the latency).
+    tokio::spawn(async move {
+        let closed = with_timeout(timeout, async {
+            websocket.close(None).await?;
+
+            while websocket.next().await.transpose()?.is_some() {}
+
+            Ok::<_, tungstenite::Error>(())
+        })
+        .await;
+
+        if let Ok(Err(err)) = closed {
+            tracing::debug!(
+                "prober poll websocket could not close cleanly (error: {})",
+                err
+            );
+        }
+    });
+
+    result
+}
+
+async fn check_messages<S>(
+    websocket: &mut WebSocketStream<S>,
+    options: &WebSocketOptions,
+) -> Result<(), Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Send message? (if configured)
+    if let Some(ref send) = options.send {
+        websocket.send(Message::Text(send.clone())).await?;
+    }
+
+    // Check response for match? (if configured)
+    if let Some(ref response_match) = options.response_match {
+        // Receive messages until one matches (other messages might be received, eg. events \
+        //   pushed by the server before the reply)
+        loop {
+            let response = match websocket.next().await.transpose()? {
+                Some(Message::Text(text)) => text,
+                Some(Message::Binary(data)) => String::from_utf8_lossy(&data).into_owned(),
+                Some(Message::Close(_)) | None => return Err(Error::Closed),
+                Some(_) => continue,
+            };
+
+            tracing::debug!(
+                "checking prober poll response for websocket target for any match: {}",
+                response
+            );
+
+            if response_match.is_match(&response) {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Upgrades the first accepted connection, then hands it over to given handler.
+    async fn serve<F, T>(handler: F) -> String
+    where
+        F: FnOnce(WebSocketStream<TcpStream>) -> T + Send + 'static,
+        T: Future<Output = ()> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/socket", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+
+            handler(tokio_tungstenite::accept_async(stream).await.unwrap()).await;
+        });
+
+        url
+    }
+
+    // Reads messages until the client closes the connection
+    async fn drain(mut websocket: WebSocketStream<TcpStream>) {
+        while let Some(Ok(_)) = websocket.next().await {}
+    }
+
+    async fn probe(url: &str, send: Option<&str>, response_match: Option<&str>) -> Status {
+        let options = WebSocketOptions {
+            send: send.map(str::to_string),
+            response_match: response_match.map(|pattern| Regex::new(pattern).unwrap()),
+        };
+
+        proceed_replica_probe_poll_websocket(
+            url,
+            TIMEOUT,
+            &HeaderMap::new(),
+            &TlsOptions::default(),
+            &options,
+        )
+        .await
+        .0
+    }
+
+    #[tokio::test]
+    async fn upgrade_only() {
+        let url = serve(drain).await;
+
+        assert_eq!(probe(&url, None, None).await, Status::Healthy);
+    }
+
+    #[tokio::test]
+    async fn send_and_match_response() {
+        let url = serve(|mut websocket| async move {
+            let request = websocket.next().await.unwrap().unwrap();
+
+            assert_eq!(request, Message::Text("ping".to_string()));
+
+            // Events pushed before the reply are skipped
+            websocket
+                .send(Message::Text("event".to_string()))
+                .await
+                .unwrap();
+            websocket
+                .send(Message::Binary(b"pong".to_vec()))
+                .await
+                .unwrap();
+
+            drain(websocket).await;
+        })
+        .await;
+
+        assert_eq!(
+            probe(&url, Some("ping"), Some("^pong$")).await,
+            Status::Healthy
+        );
+    }
+
+    #[tokio::test]
+    async fn unmatched_response_times_out() {
+        let url = serve(|mut websocket| async move {
+            websocket
+                .send(Message::Text("event".to_string()))
+                .await
+                .unwrap();
+
+            drain(websocket).await;
+        })
+        .await;
+
+        let options = WebSocketOptions {
+            send: None,
+            response_match: Some(Regex::new("^pong$").unwrap()),
+        };
+
+        let (status, _, message) = proceed_replica_probe_poll_websocket(
+            &url,
+            TIMEOUT,
+            &HeaderMap::new(),
+            &TlsOptions::default(),
+            &options,
+        )
+        .await;
+
+        assert_eq!(status, Status::Dead);
+        assert_eq!(message.as_deref(), Some("websocket: timed out after 1s"));
+    }
+
+    #[tokio::test]
+    async fn server_closes_immediately() {
+        let url = serve(|mut websocket| async move {
+            websocket.close(None).await.unwrap();
+
+            drain(websocket).await;
+        })
+        .await;
+
+        assert_eq!(probe(&url, None, Some("^pong$")).await, Status::Dead);
+    }
+
+    #[tokio::test]
+    async fn server_does_not_upgrade() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/socket", listener.local_addr().unwrap());
+
+        // Notice: the connection is accepted, then closed before any upgrade
+        tokio::spawn(async move {
+            drop(listener.accept().await.unwrap());
+        });
+
+        assert_eq!(probe(&url, None, None).await, Status::Dead);
+    }
+}
@@ -20,6 +20,7 @@ use std::sync::Arc;
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use serde::Serialize;
+use time::format_description::FormatItem;
 
 pub static STORE: Lazy<Arc<RwLock<Store>>> = Lazy::new(|| {
     Arc::new(RwLock::new(Store {
@@ -27,14 +28,34 @@ pub static STORE: Lazy<Arc<RwLock<Store>>> = Lazy::new(|| {
     }))
 });
 
+static DATE_NOW_FORMATTER: Lazy<Vec<FormatItem<'static>>> = Lazy::new(|| {
+    time::format_description::parse(
+        "[day padding:none] [month repr:long] [year], [hour]:[minute]:[second] UTC[offset_hour sign:mandatory]:[offset_minute]",
+    )
+    .expect("invalid time format")
+});
+
 pub struct Store {
     pub announcements: Vec<Announcement>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct Announcement {
     pub id: String,
     pub title: String,
     pub text: String,
     pub date: Option<String>,
 }
+
+impl Announcement {
+    pub fn new(title: String, text: String) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            title,
+            text,
+            date: time::OffsetDateTime::now_utc()
+                .format(&DATE_NOW_FORMATTER)
+                .ok(),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// ---
+//
+// Fork of: Vigil
+//
+// Microservices Status Page
+// Copyright: 2018, Valerian Saliou <valerian@valeriansaliou.name>
+// License: Mozilla Public License v2.0 (MPL v2.0)
+
+use poem::{
+    async_trait,
+    http::StatusCode,
+    web::headers::{authorization::Basic, Authorization, HeaderMapExt},
+    Endpoint, Error, Middleware, Request, Result,
+};
+use subtle::ConstantTimeEq;
+
+use crate::config::Config;
+use crate::APP_CONF;
+
+type TokenSelector = fn(&Config) -> &str;
+
+/// Guards endpoints with an HTTP Basic authentication token.
+///
+/// Notice: the token is passed as the password, while the username is ignored; this is the \
+///   scheme that Vigil clients (eg. Vigil Reporter and Vigil Local) use. The token is selected \
+///   from the current configuration upon each request, as the configuration can be reloaded. \
+///   Tokens are compared in constant time, so that timings do not leak how much of a token matched.
+pub struct BasicAuth {
+    token: TokenSelector,
+}
+
+impl BasicAuth {
+    pub fn new(token: TokenSelector) -> Self {
+        Self { token }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for BasicAuth {
+    type Output = BasicAuthEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        BasicAuthEndpoint {
+            ep,
+            token: self.token,
+        }
+    }
+}
+
+pub struct BasicAuthEndpoint<E> {
+    ep: E,
+    token: TokenSelector,
+}
+
+#[async_trait]
+impl<E: Endpoint> Endpoint for BasicAuthEndpoint<E> {
+    type Output = E::Output;
+
+    async fn call(&self, req: Request) -> Result<Self::Output> {
+        match req.headers().typed_get::<Authorization<Basic>>() {
+            Some(auth)
+                if bool::from(
+                    auth.password()
+                        .as_bytes()
+                        .ct_eq((self.token)(&APP_CONF.load()).as_bytes()),
+                ) =>
+            {
+                self.ep.call(req).await
+            }
+            _ => Err(Error::from_status(StatusCode::UNAUTHORIZED)),
+        }
+    }
+}
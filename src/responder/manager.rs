@@ -16,19 +16,22 @@
 // License: Mozilla Public License v2.0 (MPL v2.0)
 
 use poem::{
+    delete,
     endpoint::StaticFilesEndpoint,
     get,
     listener::TcpListener,
     middleware::{NormalizePath, TrailingSlash},
-    EndpointExt, Route, Server,
+    post, put, Endpoint, EndpointExt, Route, Server,
 };
 use tera::Tera;
 
+use super::auth::BasicAuth;
 use super::routes;
 use crate::APP_CONF;
 
-pub async fn run() -> std::io::Result<()> {
+pub(crate) fn make_app() -> impl Endpoint {
     let templates: String = APP_CONF
+        .load()
         .assets
         .path
         .canonicalize()
@@ -41,16 +44,50 @@ pub async fn run() -> std::io::Result<()> {
 
     let tera = Tera::new(&templates).unwrap();
 
-    let app = Route::new()
+    // Reporter routes (Vigil-compatible, authenticated with the reporter token)
+    let reporter = Route::new()
+        .at("/:probe_id/:node_id", post(routes::reporter_report))
+        .at(
+            "/:probe_id/:node_id/:replica_id",
+            delete(routes::reporter_flush),
+        )
+        .with(BasicAuth::new(|conf| &conf.server.reporter_token));
+
+    // Manager routes (authenticated with the manager token)
+    let manager = Route::new()
+        .at("/announcements", get(routes::manager_announcements))
+        .at("/announcement", post(routes::manager_announcement_insert))
+        .at(
+            "/announcement/:announcement_id",
+            put(routes::manager_announcement_update).delete(routes::manager_announcement_retract),
+        )
+        .at("/acknowledge", post(routes::manager_acknowledge))
+        .at("/reload", post(routes::manager_reload))
+        .with(BasicAuth::new(|conf| &conf.server.manager_token));
+
+    Route::new()
         .at("/", get(routes::index))
         .at("/status/text", get(routes::status_text))
+        .at("/status/json", get(routes::status_json))
+        .at("/status/json/:probe_id", get(routes::status_json_service))
         .at("/badge/:kind", get(routes::badge))
-        .nest("/assets", StaticFilesEndpoint::new(&APP_CONF.assets.path))
-        .data(tera.clone())
-        .with(NormalizePath::new(TrailingSlash::Trim));
+        .nest("/reporter", reporter)
+        .nest("/manager", manager)
+        .nest(
+            "/assets",
+            StaticFilesEndpoint::new(&APP_CONF.load().assets.path),
+        )
+        .data(tera)
+        .with(NormalizePath::new(TrailingSlash::Trim))
+}
 
-    Server::new(TcpListener::bind(APP_CONF.server.inet))
-        .run_with_graceful_shutdown(app, async { tokio::signal::ctrl_c().await.unwrap() }, None)
+pub async fn run() -> std::io::Result<()> {
+    Server::new(TcpListener::bind(APP_CONF.load().server.inet))
+        .run_with_graceful_shutdown(
+            make_app(),
+            async { tokio::signal::ctrl_c().await.unwrap() },
+            None,
+        )
         .await?;
 
     Ok(())
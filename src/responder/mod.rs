@@ -16,7 +16,9 @@
 // License: Mozilla Public License v2.0 (MPL v2.0)
 
 mod announcements;
+mod auth;
 mod context;
+mod payload;
 mod routes;
 
 pub mod manager;
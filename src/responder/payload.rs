@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// ---
+//
+// Fork of: Vigil
+//
+// Microservices Status Page
+// Copyright: 2018, Valerian Saliou <valerian@valeriansaliou.name>
+// License: Mozilla Public License v2.0 (MPL v2.0)
+
+use std::time::SystemTime;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+
+use crate::prober::mode::Mode;
+use crate::prober::states::{
+    ServiceStates, ServiceStatesProbe, ServiceStatesProbeNode, ServiceStatesProbeNodeReplica,
+};
+use crate::prober::status::Status;
+
+#[derive(Deserialize)]
+pub struct ReporterRequestPayload {
+    pub replica: String,
+    pub interval: u64,
+    pub health: Option<Status>,
+    pub load: Option<ReporterRequestPayloadLoad>,
+}
+
+#[derive(Deserialize)]
+pub struct ReporterRequestPayloadLoad {
+    pub cpu: f32,
+    pub ram: f32,
+}
+
+#[derive(Deserialize)]
+pub struct ManagerAnnouncementRequestPayload {
+    pub title: String,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct ManagerAnnouncementInsertResponsePayload {
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ManagerAcknowledgeRequestPayload {
+    pub by: String,
+    pub duration: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponsePayload<'a> {
+    pub status: &'a Status,
+    pub date: Option<&'a str>,
+    pub probes: IndexMap<&'a str, StatusResponsePayloadProbe<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponsePayloadProbe<'a> {
+    pub id: &'a str,
+    pub label: &'a str,
+    pub status: &'a Status,
+    pub nodes: IndexMap<&'a str, StatusResponsePayloadNode<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponsePayloadNode<'a> {
+    pub label: &'a str,
+    pub mode: &'a Mode,
+    pub status: &'a Status,
+    pub replicas: Vec<StatusResponsePayloadReplica<'a>>,
+}
+
+// Notice: replica identifiers are not exposed, as they might contain internal URLs (eg. poll \
+//   replicas); this matches what is visible on the status page.
+#[derive(Serialize)]
+pub struct StatusResponsePayloadReplica<'a> {
+    pub status: &'a Status,
+    pub latency: Option<u64>,
+    pub tls_expiry_days: Option<u32>,
+    pub packet_loss: Option<u8>,
+    pub jitter: Option<f64>,
+    pub checked: Option<String>,
+}
+
+impl<'a> From<&'a ServiceStates> for StatusResponsePayload<'a> {
+    fn from(states: &'a ServiceStates) -> Self {
+        Self {
+            status: &states.status,
+            date: states.date.as_deref(),
+            probes: states
+                .probes
+                .iter()
+                .map(|(probe_id, probe)| (probe_id.as_str(), probe.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a ServiceStatesProbe> for StatusResponsePayloadProbe<'a> {
+    fn from(probe: &'a ServiceStatesProbe) -> Self {
+        Self {
+            id: &probe.id,
+            label: &probe.label,
+            status: &probe.status,
+            nodes: probe
+                .nodes
+                .iter()
+                .map(|(node_id, node)| (node_id.as_str(), node.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a ServiceStatesProbeNode> for StatusResponsePayloadNode<'a> {
+    fn from(node: &'a ServiceStatesProbeNode) -> Self {
+        Self {
+            label: &node.label,
+            mode: &node.mode,
+            status: &node.status,
+            replicas: node.replicas.values().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a ServiceStatesProbeNodeReplica> for StatusResponsePayloadReplica<'a> {
+    fn from(replica: &'a ServiceStatesProbeNodeReplica) -> Self {
+        Self {
+            status: &replica.status,
+            latency: replica.metrics.latency,
+            tls_expiry_days: replica.metrics.tls.as_ref().map(|tls| tls.expiry_days),
+            packet_loss: replica.metrics.icmp.as_ref().map(|icmp| icmp.loss),
+            jitter: replica.metrics.icmp.as_ref().and_then(|icmp| icmp.jitter),
+            checked: replica.checked.and_then(format_system_time),
+        }
+    }
+}
+
+fn format_system_time(time: SystemTime) -> Option<String> {
+    time::OffsetDateTime::from(time).format(&Rfc3339).ok()
+}
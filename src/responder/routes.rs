@@ -15,28 +15,42 @@
 // Copyright: 2021, Valerian Saliou <valerian@valeriansaliou.name>
 // License: Mozilla Public License v2.0 (MPL v2.0)
 
+use std::time::Duration;
+
 use http::header::HeaderName;
 use poem::{
     error::InternalServerError,
     handler,
-    web::{Data, Html, Path, StaticFileRequest},
+    http::StatusCode,
+    web::{Data, Html, Json, Path, StaticFileRequest},
     FromRequest, IntoResponse, Request, Response,
 };
 use tera::Tera;
 
-use super::announcements::STORE as ANNOUNCEMENTS_STORE;
+use super::announcements::{Announcement, STORE as ANNOUNCEMENTS_STORE};
 use super::context::{IndexContext, INDEX_CONFIG, INDEX_ENVIRONMENT};
+use super::payload::{
+    ManagerAcknowledgeRequestPayload, ManagerAnnouncementInsertResponsePayload,
+    ManagerAnnouncementRequestPayload, ReporterRequestPayload, StatusResponsePayload,
+    StatusResponsePayloadProbe,
+};
+use crate::aggregator::manager::{acknowledge, AcknowledgeError};
 use crate::prober::manager::STORE as PROBER_STORE;
-use crate::APP_CONF;
+use crate::prober::rabbitmq::dispatch as dispatch_rabbitmq;
+use crate::prober::report::{
+    handle_flush as handle_flush_report, handle_health as handle_health_report,
+    handle_load as handle_load_report, HandleFlushError, HandleHealthError, HandleLoadError,
+};
+use crate::{reload_config, APP_CONF};
 
 #[handler]
-pub(crate) fn index(tera: Data<&Tera>) -> poem::Result<Html<String>> {
+pub(crate) fn index(tera: Data<&Tera>) -> Response {
     // Notice acquire lock in a block to release it ASAP (ie. before template renders)
     let context = IndexContext {
         states: &PROBER_STORE.read().states,
         announcements: &ANNOUNCEMENTS_STORE.read().announcements,
-        environment: &*INDEX_ENVIRONMENT,
-        config: &*INDEX_CONFIG,
+        environment: &INDEX_ENVIRONMENT,
+        config: &INDEX_CONFIG,
     };
 
     let render = tera.render(
@@ -45,8 +59,8 @@ pub(crate) fn index(tera: Data<&Tera>) -> poem::Result<Html<String>> {
     );
 
     match render {
-        Ok(s) => Ok(Html(s)),
-        Err(e) => Err(InternalServerError(e)),
+        Ok(s) => Html(s).into_response(),
+        Err(e) => InternalServerError(e).into_response(),
     }
 }
 
@@ -55,6 +69,24 @@ pub(crate) fn status_text() -> &'static str {
     PROBER_STORE.read().states.status.as_str()
 }
 
+#[handler]
+pub(crate) fn status_json() -> Response {
+    // Notice: the response is serialized while the lock is held, as it borrows from the store
+    let store = PROBER_STORE.read();
+
+    Json(StatusResponsePayload::from(&store.states)).into_response()
+}
+
+#[handler]
+pub(crate) fn status_json_service(Path(probe_id): Path<String>) -> Response {
+    let store = PROBER_STORE.read();
+
+    match store.states.probes.get(&probe_id) {
+        Some(probe) => Json(StatusResponsePayloadProbe::from(probe)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 #[handler]
 pub(crate) async fn badge(Path(kind): Path<String>) -> Response {
     // Notice acquire lock in a block to release it ASAP (ie. before OS access to file)
@@ -65,17 +97,658 @@ pub(crate) async fn badge(Path(kind): Path<String>) -> Response {
         .unwrap();
 
     let badge_path = APP_CONF
+        .load()
         .assets
         .path
         .join("images")
         .join("badges")
         .join(format!("{}-{}-default.svg", kind, status));
 
-    let resp = req.create_response(&badge_path, false).unwrap();
+    let resp = req.create_response(badge_path, false).unwrap();
     let mut resp = resp.into_response();
 
     let headers = resp.headers_mut();
-    headers.remove(&HeaderName::from_lowercase(b"last-modified").unwrap());
+    headers.remove(HeaderName::from_lowercase(b"last-modified").unwrap());
 
     resp
 }
+
+#[handler]
+pub(crate) fn reporter_report(
+    Path((probe_id, node_id)): Path<(String, String)>,
+    Json(data): Json<ReporterRequestPayload>,
+) -> StatusCode {
+    // Route report to handler (core)
+    // Notice: 'push' nodes (ie. Vigil Reporter) report their load, while 'local' nodes (ie. \
+    //   Vigil Local) report their health.
+    if let Some(ref load) = data.load {
+        match handle_load_report(
+            &probe_id,
+            &node_id,
+            &data.replica,
+            data.interval,
+            load.cpu,
+            load.ram,
+        ) {
+            Ok(()) => {
+                // Trigger a RabbitMQ queue check? (if the plugin is enabled)
+                // Notice: this is done in the background, as the check might take time.
+                if APP_CONF.load().plugins.rabbitmq.is_some() {
+                    tokio::spawn(async move {
+                        dispatch_rabbitmq(&probe_id, &node_id, &data.replica).await
+                    });
+                }
+
+                StatusCode::OK
+            }
+            Err(HandleLoadError::InvalidLoad) => StatusCode::BAD_REQUEST,
+            Err(HandleLoadError::WrongMode) => StatusCode::PRECONDITION_FAILED,
+            Err(HandleLoadError::NotFound) => StatusCode::NOT_FOUND,
+        }
+    } else if let Some(ref health) = data.health {
+        match handle_health_report(&probe_id, &node_id, &data.replica, data.interval, health) {
+            Ok(()) => StatusCode::OK,
+            Err(HandleHealthError::WrongMode) => StatusCode::PRECONDITION_FAILED,
+            Err(HandleHealthError::NotFound) => StatusCode::NOT_FOUND,
+        }
+    } else {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+#[handler]
+pub(crate) fn reporter_flush(
+    Path((probe_id, node_id, replica_id)): Path<(String, String, String)>,
+) -> StatusCode {
+    // Flush reported replica from handler (core)
+    match handle_flush_report(&probe_id, &node_id, &replica_id) {
+        Ok(()) => StatusCode::OK,
+        Err(HandleFlushError::WrongMode) => StatusCode::PRECONDITION_FAILED,
+        Err(HandleFlushError::NotFound) => StatusCode::NOT_FOUND,
+    }
+}
+
+#[handler]
+pub(crate) fn manager_announcements() -> Json<Vec<Announcement>> {
+    Json(ANNOUNCEMENTS_STORE.read().announcements.clone())
+}
+
+#[handler]
+pub(crate) fn manager_announcement_insert(
+    Json(data): Json<ManagerAnnouncementRequestPayload>,
+) -> Response {
+    // Validate data (title is required, as it is used as the announcement heading)
+    if data.title.trim().is_empty() {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let announcement = Announcement::new(data.title, data.text);
+    let announcement_id = announcement.id.clone();
+
+    tracing::info!("inserting announcement: {}", announcement_id);
+
+    ANNOUNCEMENTS_STORE.write().announcements.push(announcement);
+
+    Json(ManagerAnnouncementInsertResponsePayload {
+        id: announcement_id,
+    })
+    .into_response()
+}
+
+#[handler]
+pub(crate) fn manager_announcement_update(
+    Path(announcement_id): Path<String>,
+    Json(data): Json<ManagerAnnouncementRequestPayload>,
+) -> StatusCode {
+    if data.title.trim().is_empty() {
+        return StatusCode::BAD_REQUEST;
+    }
+
+    let mut store = ANNOUNCEMENTS_STORE.write();
+
+    match store
+        .announcements
+        .iter_mut()
+        .find(|announcement| announcement.id == announcement_id)
+    {
+        Some(announcement) => {
+            tracing::info!("updating announcement: {}", announcement_id);
+
+            announcement.title = data.title;
+            announcement.text = data.text;
+
+            StatusCode::OK
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+#[handler]
+pub(crate) fn manager_announcement_retract(Path(announcement_id): Path<String>) -> StatusCode {
+    let mut store = ANNOUNCEMENTS_STORE.write();
+
+    // Find announcement index (if it exists)
+    match store
+        .announcements
+        .iter()
+        .position(|announcement| announcement.id == announcement_id)
+    {
+        Some(position) => {
+            tracing::info!("retracting announcement: {}", announcement_id);
+
+            store.announcements.remove(position);
+
+            StatusCode::OK
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+#[handler]
+pub(crate) fn manager_acknowledge(
+    Json(data): Json<ManagerAcknowledgeRequestPayload>,
+) -> StatusCode {
+    match acknowledge(&data.by, data.duration.map(Duration::from_secs)) {
+        Ok(()) => StatusCode::OK,
+        Err(AcknowledgeError::NotDead) => StatusCode::PRECONDITION_FAILED,
+        Err(AcknowledgeError::InvalidDuration) => StatusCode::BAD_REQUEST,
+    }
+}
+
+#[handler]
+pub(crate) async fn manager_reload() -> Response {
+    match tokio::task::spawn_blocking(reload_config).await {
+        Ok(Ok(())) => StatusCode::OK.into_response(),
+        Ok(Err(err)) => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+    use std::time::Duration;
+
+    use poem::test::TestClient;
+    use poem::web::headers::Authorization;
+    use serde_json::json;
+
+    use super::super::manager::make_app;
+    use super::*;
+    use crate::prober::manager::initialize_store;
+    use crate::prober::status::Status;
+
+    static INITIALIZE_STORE: Once = Once::new();
+
+    fn make_client() -> TestClient<impl poem::Endpoint> {
+        // Notice: the prober store is shared by all tests, thus tests use distinct replicas
+        INITIALIZE_STORE.call_once(initialize_store);
+
+        TestClient::new(make_app())
+    }
+
+    fn reporter_auth() -> Authorization<poem::web::headers::authorization::Basic> {
+        Authorization::basic("", "reporter-token")
+    }
+
+    fn manager_auth() -> Authorization<poem::web::headers::authorization::Basic> {
+        Authorization::basic("", "manager-token")
+    }
+
+    fn load_payload(replica: &str, cpu: f32, ram: f32) -> serde_json::Value {
+        json!({"replica": replica, "interval": 30, "load": {"cpu": cpu, "ram": ram}})
+    }
+
+    fn health_payload(replica: &str, health: &str) -> serde_json::Value {
+        json!({"replica": replica, "interval": 30, "health": health})
+    }
+
+    fn has_replica(node_id: &str, replica_id: &str) -> bool {
+        PROBER_STORE.read().states.probes["report"].nodes[node_id]
+            .replicas
+            .contains_key(replica_id)
+    }
+
+    #[tokio::test]
+    async fn reporter_rejects_missing_or_wrong_token() {
+        let client = make_client();
+        let payload = load_payload("auth-replica", 0.5, 0.5);
+
+        client
+            .post("/reporter/report/push")
+            .body_json(&payload)
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        client
+            .post("/reporter/report/push")
+            .typed_header(Authorization::basic("", "manager-token"))
+            .body_json(&payload)
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        client
+            .delete("/reporter/report/push/auth-replica")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        assert!(!has_replica("push", "auth-replica"));
+    }
+
+    #[tokio::test]
+    async fn reporter_report_load_stores_push_replica() {
+        make_client()
+            .post("/reporter/report/push")
+            .typed_header(reporter_auth())
+            .body_json(&load_payload("load-replica", 0.5, 0.25))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let store = PROBER_STORE.read();
+        let replica = &store.states.probes["report"].nodes["push"].replicas["load-replica"];
+
+        assert_eq!(replica.status, Status::Healthy);
+        assert_eq!(replica.load.as_ref().map(|load| load.cpu), Some(0.5));
+
+        let system = replica.metrics.system.as_ref().expect("no system metrics");
+
+        assert_eq!((system.cpu, system.ram), (50, 25));
+        assert_eq!(
+            replica.report.as_ref().map(|report| report.interval),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[tokio::test]
+    async fn reporter_report_health_stores_local_replica() {
+        make_client()
+            .post("/reporter/report/local")
+            .typed_header(reporter_auth())
+            .body_json(&health_payload("health-replica", "sick"))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        let store = PROBER_STORE.read();
+        let replica = &store.states.probes["report"].nodes["local"].replicas["health-replica"];
+
+        assert_eq!(replica.status, Status::Sick);
+        assert!(replica.load.is_none());
+    }
+
+    #[tokio::test]
+    async fn reporter_report_rejects_invalid_reports() {
+        let client = make_client();
+
+        let reports = [
+            (
+                "local",
+                load_payload("invalid-replica", 0.5, 0.5),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (
+                "push",
+                health_payload("invalid-replica", "healthy"),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (
+                "poll",
+                health_payload("invalid-replica", "healthy"),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (
+                "unknown",
+                health_payload("invalid-replica", "healthy"),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "push",
+                load_payload("invalid-replica", -1.0, 0.5),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "push",
+                json!({"replica": "invalid-replica", "interval": 30}),
+                StatusCode::BAD_REQUEST,
+            ),
+        ];
+
+        for (node_id, payload, status) in reports {
+            client
+                .post(format!("/reporter/report/{}", node_id))
+                .typed_header(reporter_auth())
+                .body_json(&payload)
+                .send()
+                .await
+                .assert_status(status);
+        }
+
+        assert!(!has_replica("push", "invalid-replica"));
+        assert!(!has_replica("local", "invalid-replica"));
+    }
+
+    #[tokio::test]
+    async fn reporter_flush_removes_replica() {
+        let client = make_client();
+
+        client
+            .post("/reporter/report/local")
+            .typed_header(reporter_auth())
+            .body_json(&health_payload("flush-replica", "healthy"))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        assert!(has_replica("local", "flush-replica"));
+
+        client
+            .delete("/reporter/report/local/flush-replica")
+            .typed_header(reporter_auth())
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        assert!(!has_replica("local", "flush-replica"));
+
+        // Already flushed replicas and configured replicas cannot be flushed
+        client
+            .delete("/reporter/report/local/flush-replica")
+            .typed_header(reporter_auth())
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        client
+            .delete("/reporter/report/poll/tcp:%2F%2F127.0.0.1:9")
+            .typed_header(reporter_auth())
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+    }
+
+    fn find_announcement(announcement_id: &str) -> Option<Announcement> {
+        ANNOUNCEMENTS_STORE
+            .read()
+            .announcements
+            .iter()
+            .find(|announcement| announcement.id == announcement_id)
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn manager_rejects_missing_or_wrong_token() {
+        let client = make_client();
+
+        client
+            .get("/manager/announcements")
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        client
+            .post("/manager/announcement")
+            .typed_header(reporter_auth())
+            .body_json(&json!({"title": "Rejected", "text": "Not published."}))
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        assert!(ANNOUNCEMENTS_STORE
+            .read()
+            .announcements
+            .iter()
+            .all(|announcement| announcement.title != "Rejected"));
+    }
+
+    #[tokio::test]
+    async fn manager_announcement_lifecycle() {
+        let client = make_client();
+
+        // Publish announcement (a title is required)
+        client
+            .post("/manager/announcement")
+            .typed_header(manager_auth())
+            .body_json(&json!({"title": " ", "text": "No title."}))
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        let response = client
+            .post("/manager/announcement")
+            .typed_header(manager_auth())
+            .body_json(&json!({"title": "Maintenance", "text": "Planned upgrade."}))
+            .send()
+            .await;
+
+        response.assert_status_is_ok();
+
+        let announcement_id = response
+            .json()
+            .await
+            .value()
+            .object()
+            .get("id")
+            .string()
+            .to_string();
+
+        let announcement = find_announcement(&announcement_id).expect("announcement not stored");
+
+        assert_eq!(announcement.title, "Maintenance");
+        assert_eq!(announcement.text, "Planned upgrade.");
+
+        // List announcements
+        client
+            .get("/manager/announcements")
+            .typed_header(manager_auth())
+            .send()
+            .await
+            .json()
+            .await
+            .value()
+            .array()
+            .assert_contains(|announcement| {
+                announcement.object().get("id").string() == announcement_id
+            });
+
+        // Edit announcement
+        client
+            .put(format!("/manager/announcement/{}", announcement_id))
+            .typed_header(manager_auth())
+            .body_json(&json!({"title": "Maintenance", "text": "Upgrade is ongoing."}))
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        assert_eq!(
+            find_announcement(&announcement_id).map(|announcement| announcement.text),
+            Some("Upgrade is ongoing.".to_string())
+        );
+
+        client
+            .put("/manager/announcement/unknown")
+            .typed_header(manager_auth())
+            .body_json(&json!({"title": "Maintenance", "text": "Unknown."}))
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+
+        // Retract announcement
+        client
+            .delete(format!("/manager/announcement/{}", announcement_id))
+            .typed_header(manager_auth())
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        assert!(find_announcement(&announcement_id).is_none());
+
+        client
+            .delete(format!("/manager/announcement/{}", announcement_id))
+            .typed_header(manager_auth())
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn manager_acknowledge_outage() {
+        let client = make_client();
+        let payload = json!({"by": "on-call", "duration": 600});
+
+        client
+            .post("/manager/acknowledge")
+            .body_json(&payload)
+            .send()
+            .await
+            .assert_status(StatusCode::UNAUTHORIZED);
+
+        // Only an ongoing outage can be acknowledged
+        // Notice: this is the only test that changes the global status, which is restored after.
+        PROBER_STORE.write().states.status = Status::Healthy;
+
+        client
+            .post("/manager/acknowledge")
+            .typed_header(manager_auth())
+            .body_json(&payload)
+            .send()
+            .await
+            .assert_status(StatusCode::PRECONDITION_FAILED);
+
+        assert!(PROBER_STORE
+            .read()
+            .states
+            .notifier
+            .acknowledgement
+            .is_none());
+
+        PROBER_STORE.write().states.status = Status::Dead;
+
+        // Durations that overflow the system time are rejected
+        client
+            .post("/manager/acknowledge")
+            .typed_header(manager_auth())
+            .body_json(&json!({"by": "on-call", "duration": u64::MAX}))
+            .send()
+            .await
+            .assert_status(StatusCode::BAD_REQUEST);
+
+        assert!(PROBER_STORE
+            .read()
+            .states
+            .notifier
+            .acknowledgement
+            .is_none());
+
+        client
+            .post("/manager/acknowledge")
+            .typed_header(manager_auth())
+            .body_json(&payload)
+            .send()
+            .await
+            .assert_status_is_ok();
+
+        {
+            let mut store = PROBER_STORE.write();
+
+            let acknowledgement = store
+                .states
+                .notifier
+                .acknowledgement
+                .take()
+                .expect("outage not acknowledged");
+
+            assert_eq!(acknowledgement.by, "on-call");
+
+            let reminder_ignore_until = store
+                .states
+                .notifier
+                .reminder_ignore_until
+                .take()
+                .expect("reminders not silenced");
+
+            assert_eq!(
+                reminder_ignore_until
+                    .duration_since(acknowledgement.time)
+                    .ok(),
+                Some(Duration::from_secs(600))
+            );
+
+            store.states.status = Status::Healthy;
+        }
+    }
+
+    #[tokio::test]
+    async fn status_json_lists_services() {
+        let client = make_client();
+
+        let response = client.get("/status/json").send().await;
+
+        response.assert_status_is_ok();
+
+        let json = response.json().await;
+        let status = json.value().object();
+
+        // Notice: the general status is not asserted, as another test changes it
+        status.get("status").string();
+
+        let probe = status.get("probes").object().get("report").object();
+
+        probe.get("id").assert_string("report");
+        probe.get("label").assert_string("Reported nodes");
+
+        let nodes = probe.get("nodes").object();
+
+        nodes.get("push").object().get("mode").assert_string("push");
+        nodes
+            .get("local")
+            .object()
+            .get("mode")
+            .assert_string("local");
+
+        let poll = nodes.get("poll").object();
+
+        poll.get("label").assert_string("Poll node");
+        poll.get("mode").assert_string("poll");
+        poll.get("replicas").array().assert_len(1);
+
+        // Replica identifiers (eg. internal URLs) are not exposed
+        assert!(poll
+            .get("replicas")
+            .array()
+            .get(0)
+            .object()
+            .get_opt("id")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn status_json_service() {
+        let client = make_client();
+
+        let response = client.get("/status/json/report").send().await;
+
+        response.assert_status_is_ok();
+
+        let json = response.json().await;
+        let probe = json.value().object();
+
+        probe.get("id").assert_string("report");
+        probe
+            .get("nodes")
+            .object()
+            .get("poll")
+            .object()
+            .get("status")
+            .assert_string("healthy");
+
+        client
+            .get("/status/json/unknown")
+            .send()
+            .await
+            .assert_status(StatusCode::NOT_FOUND);
+    }
+}
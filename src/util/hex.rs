@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Serde helpers for optional binary payloads written as hexadecimal strings (eg.
+//! `"ffffffff54536f75726365"`), where whitespace between bytes is ignored (but not within a byte).
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(value) => decode(&value)
+            .map(Some)
+            .ok_or_else(|| D::Error::custom(format!("invalid hexadecimal string: {}", value))),
+        None => Ok(None),
+    }
+}
+
+fn decode(value: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    // Notice: whitespace may only separate bytes, thus each group of digits must hold full bytes \
+    //   (eg. 'ff ff' is accepted, while 'f f' is not)
+    for group in value.split_whitespace() {
+        let digits: Vec<u8> = group
+            .chars()
+            .map(|character| character.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<_>>()?;
+
+        if !digits.len().is_multiple_of(2) {
+            return None;
+        }
+
+        bytes.extend(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_bytes() {
+        assert_eq!(decode(""), Some(Vec::new()));
+        assert_eq!(
+            decode("ffffffff54536f75726365"),
+            Some(b"\xff\xff\xff\xffTSource".to_vec())
+        );
+
+        // Both cases are accepted
+        assert_eq!(decode("0aFf"), Some(vec![0x0a, 0xff]));
+    }
+
+    #[test]
+    fn decode_ignores_whitespace_between_bytes() {
+        assert_eq!(
+            decode(" ff ff\n00\t01 "),
+            Some(vec![0xff, 0xff, 0x00, 0x01])
+        );
+        assert_eq!(decode("ffff 0001"), Some(vec![0xff, 0xff, 0x00, 0x01]));
+    }
+
+    #[test]
+    fn decode_rejects_whitespace_within_bytes() {
+        assert_eq!(decode("f f"), None);
+        assert_eq!(decode("ff f f"), None);
+        assert_eq!(decode("fff f"), None);
+    }
+
+    #[test]
+    fn decode_rejects_invalid_strings() {
+        assert_eq!(decode("fff"), None);
+        assert_eq!(decode("0x01"), None);
+        assert_eq!(decode("zz"), None);
+        assert_eq!(decode("é1"), None);
+    }
+}
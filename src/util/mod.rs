@@ -1 +1,3 @@
+pub mod hex;
 pub mod ping;
+pub mod regex_map;
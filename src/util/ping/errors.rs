@@ -1,11 +1,17 @@
 use thiserror::Error;
 
+use super::packet::IcmpError;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("internal error")]
-    InternalError,
+    Internal,
+    #[error("timed out")]
+    Timeout,
+    #[error("packet error: {0}")]
+    Packet(#[from] IcmpError),
     #[error("io error: {error}")]
-    IoError {
+    Io {
         #[from]
         #[source]
         error: ::std::io::Error,
@@ -1,9 +1,10 @@
 mod errors;
 mod packet;
 
-use std::io::Read;
+use std::mem::MaybeUninit;
 use std::net::{IpAddr, SocketAddr};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use rand::random;
 pub use socket2::Type;
@@ -16,6 +17,9 @@ const TOKEN_SIZE: usize = 24;
 const ECHO_REQUEST_BUFFER_SIZE: usize = ICMP_HEADER_SIZE + TOKEN_SIZE;
 type Token = [u8; TOKEN_SIZE];
 
+// Whether raw sockets were denied (in which case datagram sockets are used from then on)
+static RAW_SOCKET_DENIED: AtomicBool = AtomicBool::new(false);
+
 pub fn ping(
     addr: IpAddr,
     timeout: Option<Duration>,
@@ -25,10 +29,8 @@ pub fn ping(
     payload: Option<&Token>,
     socket_type: Type,
 ) -> Result<(), Error> {
-    let timeout = match timeout {
-        Some(timeout) => Some(timeout),
-        None => Some(Duration::from_secs(4)),
-    };
+    let timeout = timeout.unwrap_or(Duration::from_secs(4));
+    let deadline = Instant::now() + timeout;
 
     let dest = SocketAddr::new(addr, 0);
     let mut buffer = [0; ECHO_REQUEST_BUFFER_SIZE];
@@ -41,41 +43,195 @@ pub fn ping(
         payload: payload.unwrap_or(default_payload),
     };
 
-    let mut socket = if dest.is_ipv4() {
-        if request.encode::<IcmpV4>(&mut buffer[..]).is_err() {
-            return Err(Error::InternalError);
-        }
-        Socket::new(Domain::IPV4, socket_type, Some(Protocol::ICMPV4))?
+    if dest.is_ipv4() {
+        request.encode::<IcmpV4>(&mut buffer[..])?;
+    } else {
+        request.encode::<IcmpV6>(&mut buffer[..])?;
+    }
+
+    let (socket, socket_type) = open_socket(&dest, socket_type)?;
+
+    if dest.is_ipv4() {
+        socket.set_ttl(ttl.unwrap_or(64))?;
     } else {
-        if request.encode::<IcmpV6>(&mut buffer[..]).is_err() {
-            return Err(Error::InternalError);
+        socket.set_unicast_hops_v6(ttl.unwrap_or(64))?;
+    }
+
+    // Notice: connecting the socket restricts received packets to those sent by the target, and \
+    //   picks the local address that is needed to verify ICMPv6 checksums.
+    socket.connect(&dest.into())?;
+    socket.set_write_timeout(Some(timeout))?;
+    socket.send(&buffer)?;
+
+    // Notice: the Linux kernel replaces the identifier of requests sent from datagram sockets \
+    //   with the socket 'port', thus replies have to be matched against it (other platforms, eg. \
+    //   macOS, leave the identifier as-is).
+    let local_addr = socket.local_addr()?.as_socket();
+
+    let expected_ident =
+        if cfg!(any(target_os = "linux", target_os = "android")) && socket_type == Type::DGRAM {
+            local_addr
+                .map(|local_addr| local_addr.port())
+                .ok_or(Error::Internal)?
+        } else {
+            request.ident
+        };
+
+    // Notice: the ICMPv6 checksum covers the IPv6 pseudo-header, thus it can only be verified \
+    //   if the local address is known (replies go from target to local).
+    let addresses_v6 = match (dest.ip(), local_addr.map(|local_addr| local_addr.ip())) {
+        (IpAddr::V6(target), Some(IpAddr::V6(local))) if !local.is_unspecified() => {
+            Some((target, local))
         }
-        Socket::new(Domain::IPV6, socket_type, Some(Protocol::ICMPV6))?
+        (IpAddr::V6(target), _) => {
+            tracing::debug!(
+                "local address unknown, skipping icmpv6 checksum check for: {}",
+                target
+            );
+
+            None
+        }
+        _ => None,
     };
 
-    socket.set_ttl(ttl.unwrap_or(64))?;
-    socket.set_write_timeout(timeout)?;
-    socket.send_to(&buffer, &dest.into())?;
-    socket.set_read_timeout(timeout)?;
+    // Read packets until a matching reply is received (other packets might be received, eg. \
+    //   replies to other requests to the same target)
+    let mut buffer = [MaybeUninit::<u8>::uninit(); 2048];
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err(Error::Timeout);
+        }
+
+        socket.set_read_timeout(Some(remaining))?;
+
+        let size = match socket.recv(&mut buffer) {
+            Ok(size) => size,
+            Err(err)
+                if err.kind() == std::io::ErrorKind::WouldBlock
+                    || err.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                return Err(Error::Timeout);
+            }
+            Err(err) => return Err(err.into()),
+        };
 
-    let mut buffer: [u8; 2048] = [0; 2048];
-    let _ = socket.read(&mut buffer)?;
+        // Safety: the first 'size' bytes of the buffer were initialized by the receive call
+        let packet: Vec<u8> = buffer[..size]
+            .iter()
+            .map(|byte| unsafe { byte.assume_init() })
+            .collect();
 
-    let _reply = if dest.is_ipv4() {
-        let ipv4_packet = match IpV4Packet::decode(&buffer) {
-            Ok(packet) => packet,
-            Err(_) => return Err(Error::InternalError),
+        let reply = match dest.ip() {
+            IpAddr::V4(_) => match decode_icmp_v4(&packet, socket_type) {
+                Some(icmp_data) => EchoReply::decode::<IcmpV4>(icmp_data, None),
+                None => continue,
+            },
+            IpAddr::V6(_) => EchoReply::decode::<IcmpV6>(&packet, addresses_v6),
         };
-        match EchoReply::decode::<IcmpV4>(ipv4_packet.data) {
-            Ok(reply) => reply,
-            Err(_) => return Err(Error::InternalError),
+
+        if let Ok(reply) = reply {
+            if reply.matches(expected_ident, request.seq_cnt, request.payload) {
+                return Ok(());
+            }
         }
+    }
+}
+
+fn decode_icmp_v4(packet: &[u8], socket_type: Type) -> Option<&[u8]> {
+    // Notice: raw sockets receive the IP header, while datagram sockets receive it on some \
+    //   platforms only (eg. macOS, but not Linux); as ICMP replies start with their type (which \
+    //   is '0' for echo replies), a version nibble of '4' tells that a header precedes them.
+    let has_header = socket_type == Type::RAW || packet.first().map(|byte| byte >> 4) == Some(4);
+
+    if has_header {
+        IpV4Packet::decode(packet)
+            .ok()
+            .map(|ipv4_packet| ipv4_packet.data)
     } else {
-        match EchoReply::decode::<IcmpV6>(&buffer) {
-            Ok(reply) => reply,
-            Err(_) => return Err(Error::InternalError),
-        }
+        Some(packet)
+    }
+}
+
+fn open_socket(dest: &SocketAddr, socket_type: Type) -> Result<(Socket, Type), Error> {
+    let (domain, protocol) = if dest.is_ipv4() {
+        (Domain::IPV4, Protocol::ICMPV4)
+    } else {
+        (Domain::IPV6, Protocol::ICMPV6)
     };
 
-    Ok(())
+    if socket_type == Type::RAW && !RAW_SOCKET_DENIED.load(Ordering::Relaxed) {
+        match Socket::new(domain, Type::RAW, Some(protocol)) {
+            Ok(socket) => return Ok((socket, Type::RAW)),
+            Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                tracing::warn!(
+                    "raw icmp sockets are not permitted, falling back to datagram sockets"
+                );
+
+                RAW_SOCKET_DENIED.store(true, Ordering::Relaxed);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    } else if socket_type != Type::RAW {
+        return Ok((
+            Socket::new(domain, socket_type, Some(protocol))?,
+            socket_type,
+        ));
+    }
+
+    Ok((
+        Socket::new(domain, Type::DGRAM, Some(protocol))?,
+        Type::DGRAM,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICMP_ECHO_REPLY: [u8; 8] = [0, 0, 0xf7, 0xfe, 0, 1, 0, 0];
+
+    fn with_ipv4_header(data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![
+            0x45, 0, 0, 28, 0, 0, 0, 0, 64, 1, 0, 0, 127, 0, 0, 1, 127, 0, 0, 1,
+        ];
+
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn decode_icmp_v4_raw() {
+        let packet = with_ipv4_header(&ICMP_ECHO_REPLY);
+
+        assert_eq!(
+            decode_icmp_v4(&packet, Type::RAW),
+            Some(&ICMP_ECHO_REPLY[..])
+        );
+
+        // Raw sockets always receive the header
+        assert_eq!(decode_icmp_v4(&ICMP_ECHO_REPLY, Type::RAW), None);
+    }
+
+    #[test]
+    fn decode_icmp_v4_datagram() {
+        // Without header (eg. Linux)
+        assert_eq!(
+            decode_icmp_v4(&ICMP_ECHO_REPLY, Type::DGRAM),
+            Some(&ICMP_ECHO_REPLY[..])
+        );
+
+        // With header (eg. macOS)
+        let packet = with_ipv4_header(&ICMP_ECHO_REPLY);
+
+        assert_eq!(
+            decode_icmp_v4(&packet, Type::DGRAM),
+            Some(&ICMP_ECHO_REPLY[..])
+        );
+
+        // Truncated header
+        assert_eq!(decode_icmp_v4(&packet[..12], Type::DGRAM), None);
+    }
 }
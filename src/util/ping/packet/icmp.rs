@@ -1,4 +1,5 @@
 use std::io::Write;
+use std::net::Ipv6Addr;
 use thiserror::Error;
 
 pub const HEADER_SIZE: usize = 8;
@@ -9,6 +10,8 @@ pub enum Error {
     InvalidSize,
     #[error("invalid packet")]
     InvalidPacket,
+    #[error("checksum mismatch")]
+    ChecksumMismatch,
 }
 
 pub struct IcmpV4;
@@ -19,6 +22,7 @@ pub trait Proto {
     const ECHO_REQUEST_CODE: u8;
     const ECHO_REPLY_TYPE: u8;
     const ECHO_REPLY_CODE: u8;
+    const PSEUDO_HEADER_CHECKSUM: bool;
 }
 
 impl Proto for IcmpV4 {
@@ -26,6 +30,7 @@ impl Proto for IcmpV4 {
     const ECHO_REQUEST_CODE: u8 = 0;
     const ECHO_REPLY_TYPE: u8 = 0;
     const ECHO_REPLY_CODE: u8 = 0;
+    const PSEUDO_HEADER_CHECKSUM: bool = false;
 }
 
 impl Proto for IcmpV6 {
@@ -33,6 +38,7 @@ impl Proto for IcmpV6 {
     const ECHO_REQUEST_CODE: u8 = 0;
     const ECHO_REPLY_TYPE: u8 = 129;
     const ECHO_REPLY_CODE: u8 = 0;
+    const PSEUDO_HEADER_CHECKSUM: bool = true;
 }
 
 pub struct EchoRequest<'a> {
@@ -67,17 +73,35 @@ pub struct EchoReply<'a> {
 }
 
 impl<'a> EchoReply<'a> {
-    pub fn decode<P: Proto>(buffer: &'a [u8]) -> Result<Self, Error> {
+    /// Decodes an echo reply, verifying its checksum. ICMPv6 checksums cover the IPv6 \
+    /// pseudo-header, and thus can only be verified if the (source, destination) addresses \
+    /// of the reply are passed.
+    pub fn decode<P: Proto>(
+        buffer: &'a [u8],
+        addresses: Option<(Ipv6Addr, Ipv6Addr)>,
+    ) -> Result<Self, Error> {
         if buffer.len() < HEADER_SIZE {
             return Err(Error::InvalidSize);
         }
 
         let type_ = buffer[0];
         let code = buffer[1];
-        if type_ != P::ECHO_REPLY_TYPE && code != P::ECHO_REPLY_CODE {
+        if type_ != P::ECHO_REPLY_TYPE || code != P::ECHO_REPLY_CODE {
             return Err(Error::InvalidPacket);
         }
 
+        let valid_checksum = match (P::PSEUDO_HEADER_CHECKSUM, addresses) {
+            (false, _) => checksum(&[buffer]) == 0,
+            (true, Some((source, destination))) => {
+                checksum(&[&pseudo_header_v6(source, destination, buffer.len()), buffer]) == 0
+            }
+            (true, None) => true,
+        };
+
+        if !valid_checksum {
+            return Err(Error::ChecksumMismatch);
+        }
+
         let ident = (u16::from(buffer[4]) << 8) + u16::from(buffer[5]);
         let seq_cnt = (u16::from(buffer[6]) << 8) + u16::from(buffer[7]);
 
@@ -89,24 +113,228 @@ impl<'a> EchoReply<'a> {
             payload,
         })
     }
+
+    /// Whether this reply answers the request with given identifier, sequence and payload.
+    pub fn matches(&self, ident: u16, seq_cnt: u16, payload: &[u8]) -> bool {
+        self.ident == ident && self.seq_cnt == seq_cnt && self.payload == payload
+    }
 }
 
 fn write_checksum(buffer: &mut [u8]) {
+    buffer[2] = 0;
+    buffer[3] = 0;
+
+    let sum = checksum(&[buffer]);
+
+    buffer[2] = (sum >> 8) as u8;
+    buffer[3] = (sum & 0xff) as u8;
+}
+
+// Computes the internet checksum over given parts (parts must have an even length, except for \
+//   the last one); a valid packet including its checksum sums up to zero.
+fn checksum(parts: &[&[u8]]) -> u16 {
     let mut sum = 0u32;
-    for word in buffer.chunks(2) {
-        let mut part = u16::from(word[0]) << 8;
-        if word.len() > 1 {
-            part += u16::from(word[1]);
+    for part in parts {
+        for word in part.chunks(2) {
+            let mut value = u16::from(word[0]) << 8;
+            if word.len() > 1 {
+                value += u16::from(word[1]);
+            }
+            sum = sum.wrapping_add(u32::from(value));
         }
-        sum = sum.wrapping_add(u32::from(part));
     }
 
     while (sum >> 16) > 0 {
         sum = (sum & 0xffff) + (sum >> 16);
     }
 
-    let sum = !sum as u16;
+    !sum as u16
+}
 
-    buffer[2] = (sum >> 8) as u8;
-    buffer[3] = (sum & 0xff) as u8;
+fn pseudo_header_v6(source: Ipv6Addr, destination: Ipv6Addr, length: usize) -> [u8; 40] {
+    let mut header = [0; 40];
+
+    header[0..16].copy_from_slice(&source.octets());
+    header[16..32].copy_from_slice(&destination.octets());
+    header[32..36].copy_from_slice(&(length as u32).to_be_bytes());
+
+    // Next header value for ICMPv6
+    header[39] = 58;
+
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"overvakt-echo-payload-24";
+
+    fn local() -> Ipv6Addr {
+        "2001:db8::1".parse().unwrap()
+    }
+
+    fn target() -> Ipv6Addr {
+        "2001:db8::2".parse().unwrap()
+    }
+
+    fn reply_v4(ident: u16, seq_cnt: u16) -> Vec<u8> {
+        let mut buffer = vec![0; HEADER_SIZE + PAYLOAD.len()];
+
+        EchoRequest {
+            ident,
+            seq_cnt,
+            payload: PAYLOAD,
+        }
+        .encode::<IcmpV4>(&mut buffer)
+        .unwrap();
+
+        buffer[0] = IcmpV4::ECHO_REPLY_TYPE;
+        write_checksum(&mut buffer);
+
+        buffer
+    }
+
+    fn reply_v6(ident: u16, seq_cnt: u16, source: Ipv6Addr, destination: Ipv6Addr) -> Vec<u8> {
+        let mut buffer = vec![0; HEADER_SIZE + PAYLOAD.len()];
+
+        EchoRequest {
+            ident,
+            seq_cnt,
+            payload: PAYLOAD,
+        }
+        .encode::<IcmpV6>(&mut buffer)
+        .unwrap();
+
+        buffer[0] = IcmpV6::ECHO_REPLY_TYPE;
+        buffer[2] = 0;
+        buffer[3] = 0;
+
+        let sum = checksum(&[
+            &pseudo_header_v6(source, destination, buffer.len()),
+            &buffer,
+        ]);
+
+        buffer[2..4].copy_from_slice(&sum.to_be_bytes());
+
+        buffer
+    }
+
+    #[test]
+    fn checksum_matches_reference() {
+        // Notice: sample from RFC 1071, section 3 (sums up to 0xddf2)
+        assert_eq!(
+            checksum(&[&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]]),
+            !0xddf2
+        );
+
+        // Odd-length data gets padded with a zero byte
+        assert_eq!(checksum(&[&[0x01]]), !0x0100);
+        assert_eq!(checksum(&[&[0x00, 0x01], &[0x02]]), !0x0201);
+    }
+
+    #[test]
+    fn checksum_of_encoded_request_is_zero() {
+        let mut buffer = [0; HEADER_SIZE + 24];
+
+        EchoRequest {
+            ident: 0x1234,
+            seq_cnt: 7,
+            payload: PAYLOAD,
+        }
+        .encode::<IcmpV4>(&mut buffer)
+        .unwrap();
+
+        assert_eq!(buffer[0], IcmpV4::ECHO_REQUEST_TYPE);
+        assert_eq!(&buffer[4..8], &[0x12, 0x34, 0x00, 0x07]);
+        assert_eq!(checksum(&[&buffer]), 0);
+    }
+
+    #[test]
+    fn pseudo_header_v6_layout() {
+        let header = pseudo_header_v6(target(), local(), 32);
+
+        assert_eq!(&header[0..16], &target().octets());
+        assert_eq!(&header[16..32], &local().octets());
+        assert_eq!(&header[32..36], &[0, 0, 0, 32]);
+        assert_eq!(&header[36..40], &[0, 0, 0, 58]);
+    }
+
+    #[test]
+    fn decode_v4_reply() {
+        let buffer = reply_v4(0x1234, 7);
+        let reply = EchoReply::decode::<IcmpV4>(&buffer, None).unwrap();
+
+        assert_eq!(reply.ident, 0x1234);
+        assert_eq!(reply.seq_cnt, 7);
+        assert_eq!(reply.payload, PAYLOAD);
+    }
+
+    #[test]
+    fn decode_rejects_checksum_mismatch() {
+        let mut buffer = reply_v4(0x1234, 7);
+        buffer[HEADER_SIZE] ^= 0xff;
+
+        assert!(matches!(
+            EchoReply::decode::<IcmpV4>(&buffer, None),
+            Err(Error::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn decode_rejects_requests_and_truncated_packets() {
+        let mut request = reply_v4(0x1234, 7);
+        request[0] = IcmpV4::ECHO_REQUEST_TYPE;
+        write_checksum(&mut request);
+
+        assert!(matches!(
+            EchoReply::decode::<IcmpV4>(&request, None),
+            Err(Error::InvalidPacket)
+        ));
+
+        let buffer = reply_v4(0x1234, 7);
+
+        for size in 0..HEADER_SIZE {
+            assert!(matches!(
+                EchoReply::decode::<IcmpV4>(&buffer[..size], None),
+                Err(Error::InvalidSize)
+            ));
+        }
+
+        // Notice: a truncated payload does not sum up to the checksum of the full packet
+        let reply = EchoReply::decode::<IcmpV4>(&buffer[..HEADER_SIZE], None);
+
+        assert!(matches!(reply, Err(Error::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn decode_v6_reply_checks_pseudo_header() {
+        let buffer = reply_v6(0x1234, 7, target(), local());
+
+        let reply = EchoReply::decode::<IcmpV6>(&buffer, Some((target(), local()))).unwrap();
+        assert!(reply.matches(0x1234, 7, PAYLOAD));
+
+        // Replies from another host do not sum up with the expected pseudo-header
+        let other: Ipv6Addr = "2001:db8::3".parse().unwrap();
+
+        assert!(matches!(
+            EchoReply::decode::<IcmpV6>(&buffer, Some((other, local()))),
+            Err(Error::ChecksumMismatch)
+        ));
+
+        // Checksum cannot be verified without addresses
+        assert!(EchoReply::decode::<IcmpV6>(&buffer, None).is_ok());
+    }
+
+    #[test]
+    fn reply_matches_request() {
+        let buffer = reply_v4(0x1234, 7);
+        let reply = EchoReply::decode::<IcmpV4>(&buffer, None).unwrap();
+
+        assert!(reply.matches(0x1234, 7, PAYLOAD));
+        assert!(!reply.matches(0x1235, 7, PAYLOAD));
+        assert!(!reply.matches(0x1234, 8, PAYLOAD));
+        assert!(!reply.matches(0x1234, 7, b"another-echo-payload-024"));
+        assert!(!reply.matches(0x1234, 7, &PAYLOAD[..12]));
+    }
 }
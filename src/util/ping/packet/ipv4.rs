@@ -29,7 +29,6 @@ impl IpV4Protocol {
 }
 
 pub struct IpV4Packet<'a> {
-    pub protocol: IpV4Protocol,
     pub data: &'a [u8],
 }
 
@@ -50,13 +49,11 @@ impl<'a> IpV4Packet<'a> {
             return Err(Error::InvalidHeaderSize);
         }
 
-        let protocol = match IpV4Protocol::decode(data[9]) {
-            Some(protocol) => protocol,
-            None => return Err(Error::UnknownProtocol),
-        };
+        if IpV4Protocol::decode(data[9]).is_none() {
+            return Err(Error::UnknownProtocol);
+        }
 
         Ok(Self {
-            protocol,
             data: &data[header_size..],
         })
     }
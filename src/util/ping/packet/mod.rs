@@ -1,6 +1,8 @@
 mod icmp;
 mod ipv4;
 
-pub use self::icmp::{EchoReply, EchoRequest, IcmpV4, IcmpV6, HEADER_SIZE as ICMP_HEADER_SIZE};
+pub use self::icmp::{
+    EchoReply, EchoRequest, Error as IcmpError, IcmpV4, IcmpV6, HEADER_SIZE as ICMP_HEADER_SIZE,
+};
 
-pub use self::ipv4::{IpV4Packet, IpV4Protocol};
+pub use self::ipv4::IpV4Packet;
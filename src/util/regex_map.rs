@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MPL-2.0
+//
+// Övervakt
+// Copyright © 2022 Brendan Molloy <brendan@bbqsrc.net>
+//
+//   This Source Code Form is subject to the terms of the Mozilla Public
+//   License, v. 2.0. If a copy of the MPL was not distributed with this file,
+//   You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Serde helpers for maps of regular expressions (eg. header names to value patterns), as
+//! `serde_regex` only handles single and sequenced regular expressions.
+
+use indexmap::IndexMap;
+use regex::Regex;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S>(map: &IndexMap<String, Regex>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(map.iter().map(|(key, regex)| (key, regex.as_str())))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<IndexMap<String, Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    IndexMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, pattern)| {
+            Regex::new(&pattern)
+                .map(|regex| (key, regex))
+                .map_err(D::Error::custom)
+        })
+        .collect()
+}